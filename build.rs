@@ -2,20 +2,32 @@ const COMMANDS: &[&str] = &[
     "stop_monitor",
     "start_monitor",
     "is_monitor_running",
+    "is_available",
+    "backend_status",
+    "available_selections",
     "has_text",
     "has_image",
     "has_html",
     "has_rtf",
     "has_files",
     "available_types",
+    "available_formats",
+    "read_buffer",
+    "read_all",
+    "save_snapshot",
     "read_text",
     "read_files",
     "read_files_uris",
     "read_html",
     "read_image_base64",
     "read_image_binary",
+    "read_image",
+    "read_image_info",
+    "read_image_thumbnail",
     "read_rtf",
+    "report_user_gesture",
     "write_text",
+    "write_sensitive_text",
     "write_html",
     "write_html_and_text",
     "write_rtf",
@@ -23,11 +35,46 @@ const COMMANDS: &[&str] = &[
     "write_image_base64",
     "write_files_uris",
     "write_files",
+    "write_buffer",
+    "write_contents",
+    "restore_snapshot",
+    "discard_snapshot",
     "clear",
+    "history_list",
+    "history_page",
+    "history_search",
+    "history_get",
+    "history_delete",
+    "history_pin",
+    "history_copy",
 ];
 
+/// Mirrors `ScopeEntry` in `src/scope.rs` for the schema of the `allow` and `deny` scope entries of permissions.
+#[allow(dead_code)]
+#[derive(schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ScopeEntry {
+    /// Glob of file paths that `read_files` may return and `write_files` may write, e.g. `$HOME/Documents/**`.
+    path: Option<String>,
+    /// `text`, `html`, `rtf`, `image`, `files`, a platform format name of raw buffers, or `*` for all of them.
+    format: Option<String>,
+    /// Only apply the entry to reads or to writes, it applies to both when missing.
+    access: Option<ScopeAccess>,
+    /// Largest payload in bytes that may be read or written, only used by allow entries.
+    max_size: Option<u64>,
+}
+
+#[allow(dead_code)]
+#[derive(schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum ScopeAccess {
+    Read,
+    Write,
+}
+
 fn main() {
     tauri_plugin::Builder::new(COMMANDS)
+        .global_scope_schema(schemars::schema_for!(ScopeEntry))
         .android_path("android")
         .ios_path("ios")
         .build();
@@ -1,6 +1,6 @@
 import * as v from "valibot"
-import { invoke } from "@tauri-apps/api/core"
-import { emit, listen, UnlistenFn } from "@tauri-apps/api/event"
+import { Channel, invoke } from "@tauri-apps/api/core"
+import { listen, UnlistenFn } from "@tauri-apps/api/event"
 
 const buildCmd = (cmd: string) => `plugin:clipboard|${cmd}`
 const buildEventUrl = (event: string) => `plugin:clipboard://${event}`
@@ -15,6 +15,9 @@ export const FILES_CHANGED = buildEventUrl("files-changed")
 export const IMAGE_CHANGED = buildEventUrl("image-changed")
 export const IMAGE_BINARY_CHANGED = buildEventUrl("image-changed-binary")
 export const IS_MONITOR_RUNNING_COMMAND = buildCmd("is_monitor_running")
+export const IS_AVAILABLE_COMMAND = buildCmd("is_available")
+export const BACKEND_STATUS_COMMAND = buildCmd("backend_status")
+export const AVAILABLE_SELECTIONS_COMMAND = buildCmd("available_selections")
 export const HAS_TEXT_COMMAND = buildCmd("has_text")
 export const HAS_IMAGE_COMMAND = buildCmd("has_image")
 export const HAS_HTML_COMMAND = buildCmd("has_html")
@@ -22,12 +25,21 @@ export const HAS_RTF_COMMAND = buildCmd("has_rtf")
 export const HAS_FILES_COMMAND = buildCmd("has_files")
 export const AVAILABLE_TYPES_COMMAND = buildCmd("available_types")
 export const WRITE_TEXT_COMMAND = buildCmd("write_text")
+export const WRITE_SENSITIVE_TEXT_COMMAND = buildCmd("write_sensitive_text")
 export const WRITE_HTML_COMMAND = buildCmd("write_html")
 export const WRITE_HTML_AND_TEXT_COMMAND = buildCmd("write_html_and_text")
 export const WRITE_RTF_COMMAND = buildCmd("write_rtf")
 export const WRITE_FILES_URIS_COMMAND = buildCmd("write_files_uris")
 export const WRITE_FILES_COMMAND = buildCmd("write_files")
+export const WRITE_CONTENTS_COMMAND = buildCmd("write_contents")
+export const SAVE_SNAPSHOT_COMMAND = buildCmd("save_snapshot")
+export const RESTORE_SNAPSHOT_COMMAND = buildCmd("restore_snapshot")
+export const DISCARD_SNAPSHOT_COMMAND = buildCmd("discard_snapshot")
 export const CLEAR_COMMAND = buildCmd("clear")
+export const AVAILABLE_FORMATS_COMMAND = buildCmd("available_formats")
+export const READ_BUFFER_COMMAND = buildCmd("read_buffer")
+export const WRITE_BUFFER_COMMAND = buildCmd("write_buffer")
+export const READ_ALL_COMMAND = buildCmd("read_all")
 export const READ_TEXT_COMMAND = buildCmd("read_text")
 export const READ_HTML_COMMAND = buildCmd("read_html")
 export const READ_RTF_COMMAND = buildCmd("read_rtf")
@@ -35,10 +47,22 @@ export const READ_FILES_COMMAND = buildCmd("read_files")
 export const READ_FILES_URIS_COMMAND = buildCmd("read_files_uris")
 export const READ_IMAGE_BINARY_COMMAND = buildCmd("read_image_binary")
 export const READ_IMAGE_BASE64_COMMAND = buildCmd("read_image_base64")
+export const READ_IMAGE_COMMAND = buildCmd("read_image")
+export const READ_IMAGE_INFO_COMMAND = buildCmd("read_image_info")
+export const READ_IMAGE_THUMBNAIL_COMMAND = buildCmd("read_image_thumbnail")
 export const WRITE_IMAGE_BINARY_COMMAND = buildCmd("write_image_binary")
 export const WRITE_IMAGE_BASE64_COMMAND = buildCmd("write_image_base64")
+export const HISTORY_LIST_COMMAND = buildCmd("history_list")
+export const HISTORY_PAGE_COMMAND = buildCmd("history_page")
+export const HISTORY_SEARCH_COMMAND = buildCmd("history_search")
+export const HISTORY_GET_COMMAND = buildCmd("history_get")
+export const HISTORY_DELETE_COMMAND = buildCmd("history_delete")
+export const HISTORY_PIN_COMMAND = buildCmd("history_pin")
+export const HISTORY_COPY_COMMAND = buildCmd("history_copy")
+export const REPORT_USER_GESTURE_COMMAND = buildCmd("report_user_gesture")
 export const CLIPBOARD_MONITOR_STATUS_UPDATE_EVENT = buildEventUrl("clipboard-monitor/status")
 export const MONITOR_UPDATE_EVENT = buildEventUrl("clipboard-monitor/update")
+export const CLIPBOARD_EXPIRED_EVENT = buildEventUrl("clipboard-expired")
 export const ClipboardChangedPayloadSchema = v.object({ value: v.string() })
 export const ClipboardBinaryChangedPayloadSchema = v.object({
   value: v.array(v.number())
@@ -48,32 +72,178 @@ export const ClipboardChangedFilesPayloadSchema = v.object({
 })
 export type ClipboardChangedPayload = v.InferOutput<typeof ClipboardChangedPayloadSchema>
 
-export function hasText() {
-  return invoke<boolean>(HAS_TEXT_COMMAND)
+type LocalHandler = (payload: any) => void
+/** handlers of the clipboard events, they stay inside this webview instead of being broadcast to every webview */
+const localHandlers = new Map<string, Set<LocalHandler>>()
+
+function emitLocal(event: string, payload: unknown) {
+  localHandlers.get(event)?.forEach((handler) => handler(payload))
+}
+
+function listenLocal<T>(event: string, handler: (payload: T) => void): Promise<UnlistenFn> {
+  const handlers = localHandlers.get(event) ?? new Set<LocalHandler>()
+  localHandlers.set(event, handlers)
+  handlers.add(handler)
+  return Promise.resolve(() => {
+    handlers.delete(handler)
+  })
+}
+
+/**
+ * Events of the monitor arrive over a channel of this webview once `startMonitor` was called in it,
+ * restricted to what its scope allows. Nothing is emitted to other webviews.
+ */
+function onMonitorUpdate(handler: (event: ClipboardChangeEvent) => void): Promise<UnlistenFn> {
+  return listenLocal(MONITOR_UPDATE_EVENT, handler)
+}
+
+/** the channel of this webview, Tauri core sends every change event over the one of its latest `startMonitor` */
+let monitorChannel: Channel<ClipboardChangeEvent> | undefined
+
+function monitorUpdates(): Channel<ClipboardChangeEvent> {
+  if (!monitorChannel) {
+    monitorChannel = new Channel<ClipboardChangeEvent>()
+    monitorChannel.onmessage = (event) => emitLocal(MONITOR_UPDATE_EVENT, event)
+  }
+  return monitorChannel
+}
+
+export type ClipboardErrorKind =
+  | "io"
+  | "pluginInvoke"
+  | "formatUnavailable"
+  | "invalidFileUri"
+  | "imageDecode"
+  | "invalidImage"
+  | "imageEncode"
+  | "base64Decode"
+  | "invalidRequest"
+  | "selectionUnavailable"
+  | "lockPoisoned"
+  | "backendUnavailable"
+  | "monitorAlreadyRunning"
+  | "snapshotNotFound"
+  | "historyEntryNotFound"
+  | "historyStore"
+  | "scopeDenied"
+  | "payloadTooLarge"
+  | "accessDenied"
+  /** the compositor or the app offering the data did not answer in time, the call can be retried */
+  | "timeout"
+  | "clipboard"
+
+/**
+ * Error thrown (as a rejected promise) by every clipboard command.
+ * Branch on `kind` instead of matching `message`, which is meant for humans.
+ */
+export type ClipboardError = {
+  kind: ClipboardErrorKind
+  message: string
+}
+
+export function isClipboardError(err: unknown): err is ClipboardError {
+  return (
+    typeof err === "object" &&
+    err !== null &&
+    typeof (err as ClipboardError).kind === "string" &&
+    typeof (err as ClipboardError).message === "string"
+  )
+}
+
+export type BackendStatus = {
+  backend: string
+  available: boolean
+  /** why the backend is unavailable, e.g. no display server, null when available */
+  reason: string | null
+}
+
+/**
+ * Which selection a call works on. Every platform has the regular "clipboard",
+ * X11 also has "primary" (the last selected text, pasted with the middle mouse button) and the rarely used "secondary".
+ * Calls on a selection the platform lacks reject with a `selectionUnavailable` error.
+ */
+export type ClipboardSelection = "clipboard" | "primary" | "secondary"
+
+/** selections this platform has and that can be reached, e.g. ["clipboard", "primary", "secondary"] on Linux with X11 */
+export function availableSelections() {
+  return invoke<ClipboardSelection[]>(AVAILABLE_SELECTIONS_COMMAND)
+}
+
+/**
+ * Whether the clipboard can be used at all.
+ * When the backend fails to initialize (e.g. no X server) the app keeps running, but every command rejects with a `backendUnavailable` error.
+ */
+export function isAvailable() {
+  return invoke<boolean>(IS_AVAILABLE_COMMAND)
 }
 
-export function hasHTML() {
-  return invoke<boolean>(HAS_HTML_COMMAND)
+export function backendStatus() {
+  return invoke<BackendStatus>(BACKEND_STATUS_COMMAND)
 }
 
-export function hasRTF() {
-  return invoke<boolean>(HAS_RTF_COMMAND)
+/**
+ * Report clicks and key presses in this webview to the plugin, for apps whose read policy requires a recent user gesture.
+ * Only trusted events of the top frame are reported. Returns a function that stops tracking.
+ * Needs the `clipboard:allow-report-user-gesture` permission. The check is advisory, scripts in the webview could report gestures too.
+ */
+export function trackUserGestures(): () => void {
+  if (window.top !== window.self) {
+    return () => {}
+  }
+  let last = 0
+  const report = (event: Event) => {
+    // a gesture is valid for at least the gesture timeout, no need to report every key press
+    if (!event.isTrusted || event.timeStamp - last < 250) {
+      return
+    }
+    last = event.timeStamp
+    invoke(REPORT_USER_GESTURE_COMMAND).catch(() => {})
+  }
+  const events = ["pointerdown", "keydown"]
+  events.forEach((name) => window.addEventListener(name, report, { capture: true }))
+  return () => events.forEach((name) => window.removeEventListener(name, report, { capture: true }))
 }
 
-export function hasImage() {
-  return invoke<boolean>(HAS_IMAGE_COMMAND)
+/** The `has*` functions return `false` for formats the scope may not read. */
+export function hasText(selection?: ClipboardSelection) {
+  return invoke<boolean>(HAS_TEXT_COMMAND, { selection })
 }
 
-export function hasFiles() {
-  return invoke<boolean>(HAS_FILES_COMMAND)
+export function hasHTML(selection?: ClipboardSelection) {
+  return invoke<boolean>(HAS_HTML_COMMAND, { selection })
 }
 
-export function writeText(text: string) {
-  return invoke<void>(WRITE_TEXT_COMMAND, { text })
+export function hasRTF(selection?: ClipboardSelection) {
+  return invoke<boolean>(HAS_RTF_COMMAND, { selection })
 }
 
-export function writeHtml(html: string) {
-  return invoke<void>(WRITE_HTML_COMMAND, { html })
+export function hasImage(selection?: ClipboardSelection) {
+  return invoke<boolean>(HAS_IMAGE_COMMAND, { selection })
+}
+
+export function hasFiles(selection?: ClipboardSelection) {
+  return invoke<boolean>(HAS_FILES_COMMAND, { selection })
+}
+
+export function writeText(text: string, selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_TEXT_COMMAND, { text, selection })
+}
+
+/**
+ * Write a password or one-time code. It carries the platform's hints that keep it out of clipboard managers
+ * and the system clipboard history, and is cleared after `clearAfterMs` (the builder's setting, 30 seconds by default,
+ * when omitted; 0 keeps it until the app exits) unless something else was copied in the meantime.
+ */
+export function writeSensitiveText(
+  text: string,
+  clearAfterMs?: number,
+  selection?: ClipboardSelection
+) {
+  return invoke<void>(WRITE_SENSITIVE_TEXT_COMMAND, { text, clearAfterMs, selection })
+}
+
+export function writeHtml(html: string, selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_HTML_COMMAND, { html, selection })
 }
 
 /**
@@ -81,75 +251,150 @@ export function writeHtml(html: string) {
  * writeHtml API only writes html, readText will return nothing.
  * This API writes both html and text, so readText will return the text.
  */
-export function writeHtmlAndText(html: string, text: string) {
-  return invoke<void>(WRITE_HTML_AND_TEXT_COMMAND, { html, text })
+export function writeHtmlAndText(html: string, text: string, selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_HTML_AND_TEXT_COMMAND, { html, text, selection })
 }
 
-export function writeRtf(rtf: string) {
-  return invoke<void>(WRITE_RTF_COMMAND, { rtf })
+export function writeRtf(rtf: string, selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_RTF_COMMAND, { rtf, selection })
 }
 
-export function writeFilesURIs(filesUris: string[]) {
-  return invoke<void>(WRITE_FILES_URIS_COMMAND, { filesUris })
+export function writeFilesURIs(filesUris: string[], selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_FILES_URIS_COMMAND, { filesUris, selection })
 }
 
-export function writeFiles(filesPaths: string[]) {
-  return invoke<void>(WRITE_FILES_COMMAND, { filesPaths })
+export function writeFiles(filesPaths: string[], selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_FILES_COMMAND, { filesPaths, selection })
 }
 
-export function clear() {
-  return invoke<void>(CLEAR_COMMAND)
+export function clear(selection?: ClipboardSelection) {
+  return invoke<void>(CLEAR_COMMAND, { selection })
 }
 
-export function readText() {
-  return invoke<string>(READ_TEXT_COMMAND)
+export function readText(selection?: ClipboardSelection) {
+  return invoke<string>(READ_TEXT_COMMAND, { selection })
 }
 
-export function readHtml() {
-  return invoke<string>(READ_HTML_COMMAND)
+export function readHtml(selection?: ClipboardSelection) {
+  return invoke<string>(READ_HTML_COMMAND, { selection })
 }
 
-export function readRtf() {
-  return invoke<string>(READ_RTF_COMMAND)
+export function readRtf(selection?: ClipboardSelection) {
+  return invoke<string>(READ_RTF_COMMAND, { selection })
 }
 
-export function readFiles() {
-  return invoke<string[]>(READ_FILES_COMMAND)
+export function readFiles(selection?: ClipboardSelection) {
+  return invoke<string[]>(READ_FILES_COMMAND, { selection })
 }
 
-export function readFilesURIs() {
-  return invoke<string[]>(READ_FILES_URIS_COMMAND)
+export function readFilesURIs(selection?: ClipboardSelection) {
+  return invoke<string[]>(READ_FILES_URIS_COMMAND, { selection })
 }
 
 /**
- * read clipboard image
- * @returns image in base64 string
+ * Output format of image reads, png by default.
+ * "rgba" is uncompressed 8 bit rgba pixels without header, read it with `readImage` to get the dimensions.
+ * Webp is lossless only, use "jpeg" for lossy output or encode "rgba" pixels in the webview.
  */
-export function readImageBase64() {
-  return invoke<string>(READ_IMAGE_BASE64_COMMAND)
+export type ImageEncoding =
+  | { format: "png" }
+  /** lossy without alpha channel, quality from 1 to 100, 80 by default */
+  | { format: "jpeg"; quality?: number }
+  /** lossless webp, there is no lossy webp and a quality is rejected with an `invalidRequest` error */
+  | { format: "webp" }
+  | { format: "bmp" }
+  | { format: "rgba" }
+
+export type EncodedImage = {
+  mime: string
+  width: number
+  height: number
+  data: Uint8Array
+}
+
+/**
+ * The image commands answer with a raw ArrayBuffer instead of json: width, height and the byte length of the mime
+ * type as little endian u32, the mime type, then the image data.
+ */
+function decodeEncodedImage(buffer: ArrayBuffer): EncodedImage {
+  const view = new DataView(buffer)
+  const mimeLength = view.getUint32(8, true)
+  return {
+    width: view.getUint32(0, true),
+    height: view.getUint32(4, true),
+    mime: new TextDecoder().decode(new Uint8Array(buffer, 12, mimeLength)),
+    data: new Uint8Array(buffer, 12 + mimeLength)
+  }
+}
+
+/** read the clipboard image in the given encoding, together with its size */
+export function readImage(encoding?: ImageEncoding, selection?: ClipboardSelection) {
+  return invoke<ArrayBuffer>(READ_IMAGE_COMMAND, { encoding, selection }).then(decodeEncodedImage)
+}
+
+export type ImageInfo = {
+  width: number
+  height: number
+  /** pixel layout as named by the Rust image crate, e.g. "rgba8" or "rgb16" */
+  colorType: string
+  /** size of the decoded pixels in bytes */
+  byteSize: number
+}
+
+/** resampling filter for thumbnails, from fastest to best looking */
+export type ThumbnailFilter = "nearest" | "triangle" | "catmullRom" | "gaussian" | "lanczos3"
+
+/** dimensions and pixel layout of the clipboard image, without transferring the image */
+export function readImageInfo(selection?: ClipboardSelection) {
+  return invoke<ImageInfo>(READ_IMAGE_INFO_COMMAND, { selection })
+}
+
+/**
+ * Read a preview of the clipboard image that fits in maxWidth x maxHeight, keeping the aspect ratio.
+ * The image is resized in Rust before encoding, smaller images are not enlarged.
+ */
+export function readImageThumbnail(
+  maxWidth: number,
+  maxHeight: number,
+  options: { filter?: ThumbnailFilter; encoding?: ImageEncoding; selection?: ClipboardSelection } = {}
+) {
+  return invoke<ArrayBuffer>(READ_IMAGE_THUMBNAIL_COMMAND, {
+    maxWidth,
+    maxHeight,
+    filter: options.filter,
+    encoding: options.encoding,
+    selection: options.selection
+  }).then(decodeEncodedImage)
+}
+
+export function readImageBase64(encoding?: ImageEncoding, selection?: ClipboardSelection) {
+  return invoke<string>(READ_IMAGE_BASE64_COMMAND, { encoding, selection })
 }
 
 // export const readImageBase64 = readImage;
 
 /**
  * Read clipboard image, get the data in binary format
- * int_array (Array<number>) is received from Tauri core, Uint8Array and Blob are transformed from int_array
- * @param format data type of returned value, "int_array" is the fastest
+ * The bytes arrive as a raw ArrayBuffer, int_array (Array<number>) is kept for compatibility
+ * @param format data type of returned value, "Uint8Array" is the fastest
+ * @param encoding image format of the data, png by default
  * @returns
  */
-export function readImageBinary(format: "int_array" | "Uint8Array" | "Blob") {
-  return (
-    invoke<number[] | Uint8Array | Blob>(READ_IMAGE_BINARY_COMMAND) as Promise<number[]>
-  ).then((img_arr: number[]) => {
+export function readImageBinary(
+  format: "int_array" | "Uint8Array" | "Blob",
+  encoding?: ImageEncoding,
+  selection?: ClipboardSelection
+) {
+  return invoke<ArrayBuffer>(READ_IMAGE_BINARY_COMMAND, { encoding, selection }).then((buffer) => {
     switch (format) {
       case "int_array":
-        return img_arr
+        return Array.from(new Uint8Array(buffer))
       case "Uint8Array":
-        return new Uint8Array(img_arr)
+        return new Uint8Array(buffer)
       case "Blob":
-        return new Blob([new Uint8Array(img_arr)])
+        return new Blob([buffer])
       default:
-        return img_arr
+        return Array.from(new Uint8Array(buffer))
     }
   })
 }
@@ -175,24 +420,95 @@ export function readImageObjectURL(): Promise<string> {
 }
 
 /**
- * write image to clipboard
- * @param data image data in base64 encoded string
- * @returns Promise<void>
+ * Write a base64 encoded image, or a "data:image/...;base64," url, to the clipboard.
+ * Any format the Rust image crate reads is accepted, e.g. png, jpeg, webp, gif (first frame), bmp, tiff or ico.
+ * Rejects with an `invalidImage` error naming the detected format when the data cannot be decoded.
+ */
+export function writeImageBase64(base64: string, selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_IMAGE_BASE64_COMMAND, { base64Image: base64, selection })
+}
+
+/**
+ * Write image file bytes to the clipboard, the format is detected like in `writeImageBase64`.
+ * The bytes are sent as a raw request body without json encoding.
+ */
+export function writeImageBinary(
+  bytes: number[] | Uint8Array | ArrayBuffer,
+  selection?: ClipboardSelection
+) {
+  return invoke<void>(WRITE_IMAGE_BINARY_COMMAND, toBytes(bytes), {
+    headers: selection ? { selection } : {}
+  })
+}
+
+function toBytes(bytes: number[] | Uint8Array | ArrayBuffer): Uint8Array {
+  return bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes)
+}
+
+/** A raw format offered by the clipboard */
+export type FormatInfo = {
+  /** platform identifier, e.g. "UTF8_STRING", "public.html" or "CF_UNICODETEXT", pass it to `readBuffer` */
+  name: string
+  /** mime type the name corresponds to, null for bookkeeping targets like "TARGETS" and unknown names */
+  mime: string | null
+  /** payload size in bytes, only where it is cheap to find out */
+  size: number | null
+}
+
+/**
+ * Every raw format on the clipboard, e.g. mime types on Linux, UTIs on macOS or format names on Windows,
+ * in the order the source app offers them. Formats the scope may not read are left out.
+ */
+export function availableFormats(selection?: ClipboardSelection) {
+  return invoke<FormatInfo[]>(AVAILABLE_FORMATS_COMMAND, { selection })
+}
+
+/** Read the raw bytes of any format listed by `availableFormats` */
+export function readBuffer(format: string, selection?: ClipboardSelection) {
+  return invoke<ArrayBuffer>(READ_BUFFER_COMMAND, { format, selection }).then((buffer) => new Uint8Array(buffer))
+}
+
+/**
+ * Put raw bytes on the clipboard under a custom format name, e.g. "application/x-my-app-shape".
+ * Use `writeContents` with a `custom` item to offer them together with a text or image fallback.
+ * The bytes are sent as a raw request body, the format name goes in a header and must be ASCII.
  */
-export function writeImageBase64(base64: string) {
-  return invoke<void>(WRITE_IMAGE_BASE64_COMMAND, { base64Image: base64 })
+export function writeBuffer(
+  format: string,
+  buffer: Uint8Array | ArrayBuffer | number[],
+  selection?: ClipboardSelection
+) {
+  return invoke<void>(WRITE_BUFFER_COMMAND, toBytes(buffer), {
+    headers: selection ? { format, selection } : { format }
+  })
 }
 
-export function writeImageBinary(bytes: number[]) {
-  return invoke<void>(WRITE_IMAGE_BINARY_COMMAND, { bytes: bytes })
+/** One representation of the clipboard contents, see `writeContents` */
+export type ClipboardItemContent =
+  | { type: "text"; value: string }
+  | { type: "html"; value: string }
+  | { type: "rtf"; value: string }
+  /** base64 encoded image or data url, in any format accepted by `writeImageBase64` */
+  | { type: "image"; value: string }
+  /** file uris, in the format expected by `writeFilesURIs` */
+  | { type: "files"; value: string[] }
+  /** raw bytes of a platform specific format, e.g. a mime type on Linux */
+  | { type: "custom"; value: { format: string; data: number[] } }
+
+/**
+ * Replace the clipboard with all `contents` in one go, e.g. text together with its rtf version.
+ * Writing the formats one by one with `writeText`, `writeRtf` etc. keeps only the last one.
+ */
+export function writeContents(contents: ClipboardItemContent[], selection?: ClipboardSelection) {
+  return invoke<void>(WRITE_CONTENTS_COMMAND, { contents, selection })
 }
 
 /**
  * @deprecated since version v0.5.x
  * Brute force listen to clipboard text update.
  * Detect update by comparing current value with previous value every delay ms.
- * When there is a update, "plugin:clipboard://text-changed" is emitted.
- * You still need to listen to the event.
+ * When there is a update, "plugin:clipboard://text-changed" is emitted inside this webview.
+ * You still need to listen to the event with `onTextUpdate`.
  *
  * @param delay check interval delay
  * @returns a stop running function that can be called when component unmounts
@@ -204,7 +520,7 @@ export function startBruteForceTextMonitor(delay: number = 500) {
     try {
       const text = await readText()
       if (prevText !== text) {
-        await emit(TEXT_CHANGED, { value: text })
+        emitLocal(TEXT_CHANGED, { value: text })
       }
       prevText = text
     } catch (error) {}
@@ -218,8 +534,8 @@ export function startBruteForceTextMonitor(delay: number = 500) {
 /**
  * @deprecated since version v0.5.x
  * Brute force monitor clipboard image update by comparing current value with previous value.
- * When there is a update, "plugin:clipboard://image-changed" is emitted.
- * You still need to listen to the event.
+ * When there is a update, "plugin:clipboard://image-changed" is emitted inside this webview.
+ * You still need to listen to the event with `onImageUpdate`.
  *
  * @param delay check interval delay
  * @returns stop running function that can be called to stop the monitor
@@ -231,7 +547,7 @@ export function startBruteForceImageMonitor(delay: number = 1000) {
     try {
       const img = await readImageBase64()
       if (prevImg !== img) {
-        await emit(IMAGE_CHANGED, { value: img })
+        emitLocal(IMAGE_CHANGED, { value: img })
       }
       prevImg = img
     } catch (error) {
@@ -261,8 +577,113 @@ export type AvailableTypes = {
   files: boolean
 }
 
-export function getAvailableTypes(): Promise<AvailableTypes> {
-  return invoke<AvailableTypes>(AVAILABLE_TYPES_COMMAND)
+/**
+ * Contents the monitor reads and attaches to every `ClipboardChangeEvent`.
+ * Formats that are not enabled are still reported in `formats`.
+ */
+export type MonitorOptions = {
+  text?: boolean
+  html?: boolean
+  rtf?: boolean
+  files?: boolean
+  /** attach the image as base64 encoded png, this is expensive for large images */
+  image?: boolean
+}
+
+/**
+ * Payload of "plugin:clipboard://clipboard-monitor/update", a snapshot of the clipboard taken right after it changed.
+ */
+export type ClipboardChangeEvent = {
+  /** increases by one for every change observed since the app started */
+  seq: number
+  /** milliseconds since the unix epoch */
+  timestamp: number
+  /** "plugin" when the change was made through this plugin's write APIs */
+  origin: "external" | "plugin"
+  /** the selection that changed, "clipboard" unless the monitor of another selection was started */
+  selection: ClipboardSelection
+  /** the content carries a password manager hint, e.g. from `writeSensitiveText`; the history skips it */
+  sensitive: boolean
+  formats: AvailableTypes
+  text?: string
+  html?: string
+  rtf?: string
+  files?: string[]
+  /** base64 encoded png */
+  image?: string
+}
+
+/** Which formats `readAll` reads, every format is read unless set to false */
+export type SnapshotOptions = {
+  text?: boolean
+  html?: boolean
+  rtf?: boolean
+  files?: boolean
+  /** encoding the image as png is by far the most expensive part of a snapshot */
+  image?: boolean
+}
+
+/** Every representation on the clipboard, read at the same moment */
+export type ClipboardSnapshot = {
+  formats: AvailableTypes
+  text?: string
+  html?: string
+  rtf?: string
+  files?: string[]
+  /** base64 encoded png */
+  image?: string
+}
+
+/**
+ * Read every available format in one call, instead of `getAvailableTypes` followed by a `read*` per format,
+ * which can observe the clipboard changing in between.
+ */
+export function readAll(options?: SnapshotOptions, selection?: ClipboardSelection) {
+  return invoke<ClipboardSnapshot>(READ_ALL_COMMAND, { options, selection })
+}
+
+/**
+ * Save everything on the clipboard, including formats without a typed API, inside the plugin.
+ * @returns handle for `restoreSnapshot` or `discardSnapshot`, only valid in this webview and for this selection
+ */
+export function saveSnapshot(selection?: ClipboardSelection) {
+  return invoke<number>(SAVE_SNAPSHOT_COMMAND, { selection })
+}
+
+/** Put a saved snapshot back onto the clipboard, the handle can not be used again */
+export function restoreSnapshot(id: number, selection?: ClipboardSelection) {
+  return invoke<void>(RESTORE_SNAPSHOT_COMMAND, { id, selection })
+}
+
+export function discardSnapshot(id: number, selection?: ClipboardSelection) {
+  return invoke<void>(DISCARD_SNAPSHOT_COMMAND, { id, selection })
+}
+
+/**
+ * Run `fn` and restore the clipboard contents from before the call afterwards, also when `fn` throws.
+ * The error of `fn` takes precedence over one of the restore.
+ */
+export async function restoreAfter<T>(
+  fn: () => Promise<T>,
+  selection?: ClipboardSelection
+): Promise<T> {
+  const id = await saveSnapshot(selection)
+  let result: T
+  try {
+    result = await fn()
+  } catch (error) {
+    // the error of fn takes precedence, a restore that fails as well is only logged
+    await restoreSnapshot(id, selection).catch((restoreError) =>
+      console.error("clipboard contents could not be restored", restoreError)
+    )
+    throw error
+  }
+  await restoreSnapshot(id, selection)
+  return result
+}
+
+export function getAvailableTypes(selection?: ClipboardSelection): Promise<AvailableTypes> {
+  return invoke<AvailableTypes>(AVAILABLE_TYPES_COMMAND, { selection })
 }
 
 /**
@@ -281,70 +702,68 @@ export function listenToClipboard(
     files: true
   }
 ): Promise<UnlistenFn> {
-  return listen(MONITOR_UPDATE_EVENT, async (e) => {
-    if (e.payload === "clipboard update") {
-      const hasData = await Promise.all([hasFiles(), hasImage(), hasHTML(), hasRTF(), hasText()])
-      const flags: UpdatedTypes = {
-        files: hasData[0],
-        image: hasData[1],
-        imageBinary: hasData[1],
-        html: hasData[2],
-        rtf: hasData[3],
-        text: hasData[4]
-      }
-      await emit(SOMETHING_CHANGED, flags)
-      if (listenTypes.files && flags.files) {
-        const files = await readFiles()
-        if (files && files.length > 0) {
-          await emit(FILES_CHANGED, { value: files })
-        }
-        // flags.files = true;
-        return // ! this return is necessary, copying files also update clipboard text, but we don't want text update to be triggered
-      }
-      if (listenTypes.image && flags.image) {
-        const img = await readImageBase64()
-        if (img) await emit(IMAGE_CHANGED, { value: img })
-        // flags.image = true;
-      }
-      if (listenTypes.imageBinary && flags.imageBinary) {
-        const img = await readImageBinary("int_array")
-        if (img) await emit(IMAGE_BINARY_CHANGED, { value: img })
-        // flags.imageBinary = true;
-      }
-      if (listenTypes.html && flags.html) {
-        await emit(HTML_CHANGED, { value: await readHtml() })
-        // flags.html = true;
-      }
-      if (listenTypes.rtf && flags.rtf) {
-        await emit(RTF_CHANGED, { value: await readRtf() })
-        // flags.rtf = true;
+  return onMonitorUpdate(async (payload) => {
+    // the per-format events describe the regular clipboard only
+    if (payload.selection !== "clipboard") return
+    const { formats } = payload
+    const flags: UpdatedTypes = { ...formats, imageBinary: formats.image }
+    emitLocal(SOMETHING_CHANGED, flags)
+    // contents the monitor was not asked to attach (see startMonitor options) are read on demand
+    if (listenTypes.files && flags.files) {
+      const files = payload.files ?? (await readFiles())
+      if (files && files.length > 0) {
+        emitLocal(FILES_CHANGED, { value: files })
       }
-      if (listenTypes.text && flags.text) {
-        await emit(TEXT_CHANGED, { value: await readText() })
-        // flags.text = true;
-      }
-      // when clear() is called, this error is thrown, let ignore it
-      // if (!success) {
-      //   throw new Error("Unexpected Error: No proper clipboard type");
-      // }
+      return // ! this return is necessary, copying files also update clipboard text, but we don't want text update to be triggered
+    }
+    if (listenTypes.image && flags.image) {
+      const img = payload.image ?? (await readImageBase64())
+      if (img) emitLocal(IMAGE_CHANGED, { value: img })
+    }
+    if (listenTypes.imageBinary && flags.imageBinary) {
+      const img = await readImageBinary("int_array")
+      if (img) emitLocal(IMAGE_BINARY_CHANGED, { value: img })
+    }
+    if (listenTypes.html && flags.html) {
+      emitLocal(HTML_CHANGED, { value: payload.html ?? (await readHtml()) })
+    }
+    if (listenTypes.rtf && flags.rtf) {
+      emitLocal(RTF_CHANGED, { value: payload.rtf ?? (await readRtf()) })
+    }
+    if (listenTypes.text && flags.text) {
+      emitLocal(TEXT_CHANGED, { value: payload.text ?? (await readText()) })
     }
   })
 }
 
 /**
  * This listen to clipboard monitor update event, and trigger the callback function.
- * However from this event we don't know whether it's text or image, no real data is returned.
- * Use with listenToClipboard function.
+ * The event tells which formats are available, contents are only attached for formats enabled in `startMonitor` options.
  * @param cb callback
  * @returns unlisten function
  */
-export function onClipboardUpdate(cb: () => void) {
-  return listen(MONITOR_UPDATE_EVENT, cb)
+export function onClipboardUpdate(cb: (event: ClipboardChangeEvent) => void) {
+  return onMonitorUpdate(cb)
+}
+
+/** Payload of `plugin:clipboard://clipboard-expired` */
+export type ClipboardExpiredEvent = {
+  selection: ClipboardSelection
+  /** milliseconds since the unix epoch */
+  timestamp: number
+}
+
+/**
+ * Listen for content written by the plugin that expired and was cleared,
+ * see `Builder::expire_after` and `writeSensitiveText`.
+ */
+export function onClipboardExpired(cb: (event: ClipboardExpiredEvent) => void) {
+  return listen<ClipboardExpiredEvent>(CLIPBOARD_EXPIRED_EVENT, (event) => cb(event.payload))
 }
 
 export async function onTextUpdate(cb: (text: string) => void): Promise<UnlistenFn> {
-  return await listen(TEXT_CHANGED, (event) => {
-    const text = v.parse(ClipboardChangedPayloadSchema, event.payload).value
+  return await listenLocal(TEXT_CHANGED, (payload) => {
+    const text = v.parse(ClipboardChangedPayloadSchema, payload).value
     cb(text)
   })
 }
@@ -359,42 +778,42 @@ export async function onTextUpdate(cb: (text: string) => void): Promise<Unlisten
  * @returns
  */
 export function onSomethingUpdate(cb: (updatedTypes: UpdatedTypes) => void) {
-  return listen(SOMETHING_CHANGED, (event) => {
-    cb(event.payload as UpdatedTypes)
+  return listenLocal(SOMETHING_CHANGED, (payload) => {
+    cb(payload as UpdatedTypes)
   })
 }
 
 export function onHTMLUpdate(cb: (text: string) => void): Promise<UnlistenFn> {
-  return listen(HTML_CHANGED, (event) => {
-    const text = v.parse(ClipboardChangedPayloadSchema, event.payload).value
+  return listenLocal(HTML_CHANGED, (payload) => {
+    const text = v.parse(ClipboardChangedPayloadSchema, payload).value
     cb(text)
   })
 }
 
 export function onRTFUpdate(cb: (text: string) => void): Promise<UnlistenFn> {
-  return listen(RTF_CHANGED, (event) => {
-    const text = v.parse(ClipboardChangedPayloadSchema, event.payload).value
+  return listenLocal(RTF_CHANGED, (payload) => {
+    const text = v.parse(ClipboardChangedPayloadSchema, payload).value
     cb(text)
   })
 }
 
 export function onFilesUpdate(cb: (files: string[]) => void): Promise<UnlistenFn> {
-  return listen(FILES_CHANGED, (event) => {
-    const files = v.parse(ClipboardChangedFilesPayloadSchema, event.payload).value
+  return listenLocal(FILES_CHANGED, (payload) => {
+    const files = v.parse(ClipboardChangedFilesPayloadSchema, payload).value
     cb(files)
   })
 }
 
 export function onImageUpdate(cb: (base64ImageStr: string) => void): Promise<UnlistenFn> {
-  return listen(IMAGE_CHANGED, (event) => {
-    const base64ImageStr = v.parse(ClipboardChangedPayloadSchema, event.payload).value
+  return listenLocal(IMAGE_CHANGED, (payload) => {
+    const base64ImageStr = v.parse(ClipboardChangedPayloadSchema, payload).value
     cb(base64ImageStr)
   })
 }
 
 export function onImageBinaryUpdate(cb: (image: number[]) => void) {
-  return listen(IMAGE_BINARY_CHANGED, (event) => {
-    cb(v.parse(ClipboardBinaryChangedPayloadSchema, event.payload).value)
+  return listenLocal(IMAGE_BINARY_CHANGED, (payload) => {
+    cb(v.parse(ClipboardBinaryChangedPayloadSchema, payload).value)
   })
 }
 
@@ -402,8 +821,8 @@ export function onImageBinaryUpdate(cb: (image: number[]) => void) {
  * Used to check the status of clipboard monitor
  * @returns Whether the monitor is running
  */
-export function isMonitorRunning() {
-  return invoke<boolean>(IS_MONITOR_RUNNING_COMMAND).then((res: unknown) =>
+export function isMonitorRunning(selection?: ClipboardSelection) {
+  return invoke<boolean>(IS_MONITOR_RUNNING_COMMAND, { selection }).then((res: unknown) =>
     v.parse(v.boolean(), res)
   )
 }
@@ -412,19 +831,23 @@ export function isMonitorRunning() {
  * Start running mointor thread in Tauri core. This feature is added in v0.5.x.
  * Before v0.5.x, the monitor is started during setup when app starts.
  * After v0.5.x, this function must be called first to start monitor.
- * After monitor is started, events "plugin:clipboard://clipboard-monitor/update" will be emitted when there is clipboard update.
+ * After monitor is started, `onClipboardUpdate` and `listenToClipboard` receive the clipboard updates,
+ * only in this webview and only while the read policy lets it read.
  * "plugin:clipboard://clipboard-monitor/status" event is also emitted when monitor status updates
- * Still have to listen to these events.
+ * A monitor that is already running, started by another webview or from Rust, is joined instead.
+ * Every call must be undone by one `stopMonitor` call.
+ * @param options contents to attach to every update event, by default only the available formats are reported
  */
-export function startMonitor() {
-  return invoke<void>(START_MONITOR_COMMAND)
+export function startMonitor(options?: MonitorOptions, selection?: ClipboardSelection) {
+  return invoke<void>(START_MONITOR_COMMAND, { onEvent: monitorUpdates(), options, selection })
 }
 
 /**
- * Stop clipboard monitor thread.
+ * Undo one `startMonitor` call of this webview. The monitor thread stops once every webview that started it
+ * stopped it, unless it was started from Rust.
  */
-export function stopMonitor() {
-  return invoke<void>(STOP_MONITOR_COMMAND)
+export function stopMonitor(selection?: ClipboardSelection) {
+  return invoke<void>(STOP_MONITOR_COMMAND, { selection })
 }
 /**
  * Listen to monitor status update. Instead of calling isMonitorRunning to get status of monitor,
@@ -450,13 +873,107 @@ export function startListening(
     files: true
   }
 ): Promise<() => Promise<void>> {
-  return startMonitor()
+  return startMonitor({
+    text: listenTypes.text,
+    html: listenTypes.html,
+    rtf: listenTypes.rtf,
+    files: listenTypes.files,
+    image: listenTypes.image
+  })
     .then(() => listenToClipboard(listenTypes))
     .then((unlistenClipboard) => {
-      // return an unlisten function that stop listening to clipboard update and stop the monitor
+      // return an unlisten function that stop listening to clipboard update and stop the monitor,
+      // it keeps running for the other listeners
       return async () => {
         unlistenClipboard()
         await stopMonitor()
       }
     })
 }
+
+/**
+ * A clipboard change recorded by the monitor, with every format that was on the clipboard.
+ * Only available when the plugin is built with the `history` cargo feature.
+ */
+export type HistoryEntry = {
+  /** stable across restarts, unlike `seq` */
+  id: number
+  seq: number
+  /** milliseconds since the unix epoch */
+  timestamp: number
+  origin: "external" | "plugin"
+  /** pinned entries are never removed by the retention limits */
+  pinned: boolean
+  formats: AvailableTypes
+  text?: string
+  html?: string
+  rtf?: string
+  files?: string[]
+  /** base64 encoded png, only loaded by `historyGet`, `formats.image` tells whether there is one */
+  image?: string
+  size: number
+}
+
+export type HistoryPage = {
+  entries: HistoryEntry[]
+  offset: number
+  /** number of entries in the whole history */
+  total: number
+}
+
+/** every recorded change, newest first */
+export function historyList() {
+  return invoke<HistoryEntry[]>(HISTORY_LIST_COMMAND)
+}
+
+export function historyPage(offset: number, limit: number) {
+  return invoke<HistoryPage>(HISTORY_PAGE_COMMAND, { offset, limit })
+}
+
+export type HistoryFormat = "text" | "html" | "rtf" | "files" | "image"
+
+export type HistoryQuery = {
+  /** words that must all match, as a whole word, a word prefix or a substring. Empty matches every entry */
+  query?: string
+  /** also accept words within a small edit distance, e.g. typos */
+  fuzzy?: boolean
+  /** only entries containing at least one of these formats */
+  formats?: HistoryFormat[]
+  /** earliest timestamp in milliseconds since the unix epoch, inclusive */
+  since?: number
+  /** latest timestamp in milliseconds since the unix epoch, inclusive */
+  until?: number
+  offset?: number
+  /** defaults to 50 */
+  limit?: number
+}
+
+export type HistoryMatch = {
+  score: number
+  entry: HistoryEntry
+}
+
+/**
+ * Search text, the text of html and rtf, and file paths of every history entry on the Rust side.
+ * Results are ordered by relevance, newer entries first on ties.
+ */
+export function historySearch(query: HistoryQuery) {
+  return invoke<HistoryMatch[]>(HISTORY_SEARCH_COMMAND, { query })
+}
+
+export function historyGet(id: number) {
+  return invoke<HistoryEntry>(HISTORY_GET_COMMAND, { id })
+}
+
+export function historyDelete(id: number) {
+  return invoke<void>(HISTORY_DELETE_COMMAND, { id })
+}
+
+export function historyPin(id: number, pinned: boolean = true) {
+  return invoke<void>(HISTORY_PIN_COMMAND, { id, pinned })
+}
+
+/** write every format of a history entry back to the clipboard */
+export function historyCopy(id: number) {
+  return invoke<void>(HISTORY_COPY_COMMAND, { id })
+}
@@ -0,0 +1,220 @@
+use super::{ClipboardBackend, ClipboardSelection, WatchHandle};
+use crate::{Error, Result};
+use clipboard_rs::{common::RustImage, ClipboardContent, ContentFormat, RustImageData};
+use image::DynamicImage;
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Arc, Mutex},
+};
+
+#[derive(Default)]
+struct MemoryContents {
+    text: Option<String>,
+    html: Option<String>,
+    rtf: Option<String>,
+    image: Option<DynamicImage>,
+    files: Option<Vec<String>>,
+    other: HashMap<String, Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Watchers {
+    next_id: u64,
+    senders: Vec<(u64, mpsc::Sender<()>)>,
+}
+
+/// An in-process clipboard that never touches the system clipboard.
+///
+/// Useful for tests and headless environments. Watchers are notified from their own thread after every write,
+/// just like with the system clipboard.
+#[derive(Default)]
+pub struct MemoryBackend {
+    contents: Mutex<MemoryContents>,
+    watchers: Arc<Mutex<Watchers>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read<T>(&self, name: &str, f: impl FnOnce(&MemoryContents) -> Option<T>) -> Result<T> {
+        let contents = self.contents.lock()?;
+        f(&contents).ok_or_else(|| Error::FormatUnavailable(name.to_string()))
+    }
+
+    fn replace(&self, contents: MemoryContents) -> Result<()> {
+        *self.contents.lock()? = contents;
+        self.notify();
+        Ok(())
+    }
+
+    fn notify(&self) {
+        if let Ok(mut watchers) = self.watchers.lock() {
+            watchers
+                .senders
+                .retain(|(_, sender)| sender.send(()).is_ok());
+        }
+    }
+}
+
+impl ClipboardBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn has(&self, format: ContentFormat) -> Result<bool> {
+        let contents = self.contents.lock()?;
+        Ok(match format {
+            ContentFormat::Text => contents.text.is_some(),
+            ContentFormat::Html => contents.html.is_some(),
+            ContentFormat::Rtf => contents.rtf.is_some(),
+            ContentFormat::Image => contents.image.is_some(),
+            ContentFormat::Files => contents.files.is_some(),
+            ContentFormat::Other(name) => contents.other.contains_key(&name),
+        })
+    }
+
+    fn available_formats(&self) -> Result<Vec<String>> {
+        let contents = self.contents.lock()?;
+        let typed = [
+            (contents.text.is_some(), "text/plain"),
+            (contents.html.is_some(), "text/html"),
+            (contents.rtf.is_some(), "text/rtf"),
+            (contents.image.is_some(), "image/png"),
+            (contents.files.is_some(), "text/uri-list"),
+        ];
+        Ok(typed
+            .into_iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, name)| name.to_string())
+            .chain(contents.other.keys().cloned())
+            .collect())
+    }
+
+    fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+        let contents = self.contents.lock()?;
+        let buffer = match format {
+            "text/plain" => contents.text.clone().map(String::into_bytes),
+            "text/html" => contents.html.clone().map(String::into_bytes),
+            "text/rtf" => contents.rtf.clone().map(String::into_bytes),
+            "text/uri-list" => contents
+                .files
+                .as_ref()
+                .map(|files| files.join("\r\n").into_bytes()),
+            "image/png" => match &contents.image {
+                Some(image) => {
+                    let mut png = std::io::Cursor::new(vec![]);
+                    image
+                        .write_to(&mut png, image::ImageFormat::Png)
+                        .map_err(|err| Error::ImageEncode(err.to_string()))?;
+                    Some(png.into_inner())
+                }
+                None => None,
+            },
+            other => contents.other.get(other).cloned(),
+        };
+        buffer.ok_or_else(|| Error::FormatUnavailable(format.to_string()))
+    }
+
+    fn buffer_size(&self, format: &str) -> Option<u64> {
+        self.get_buffer(format)
+            .ok()
+            .map(|buffer| buffer.len() as u64)
+    }
+
+    fn get_text(&self) -> Result<String> {
+        self.read("text", |contents| contents.text.clone())
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.read("html", |contents| contents.html.clone())
+    }
+
+    fn get_rich_text(&self) -> Result<String> {
+        self.read("rtf", |contents| contents.rtf.clone())
+    }
+
+    fn get_image(&self) -> Result<RustImageData> {
+        self.read("image", |contents| contents.image.clone())
+            .map(RustImageData::from_dynamic_image)
+    }
+
+    fn get_files(&self) -> Result<Vec<String>> {
+        self.read("files", |contents| contents.files.clone())
+    }
+
+    fn set_text(&self, text: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Text(text)])
+    }
+
+    fn set_html(&self, html: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Html(html)])
+    }
+
+    fn set_rich_text(&self, rtf: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Rtf(rtf)])
+    }
+
+    fn set_image(&self, image: RustImageData) -> Result<()> {
+        self.set(vec![ClipboardContent::Image(image)])
+    }
+
+    fn set_files(&self, files: Vec<String>) -> Result<()> {
+        self.set(vec![ClipboardContent::Files(files)])
+    }
+
+    fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        let mut new_contents = MemoryContents::default();
+        for content in contents {
+            match content {
+                ClipboardContent::Text(text) => new_contents.text = Some(text),
+                ClipboardContent::Html(html) => new_contents.html = Some(html),
+                ClipboardContent::Rtf(rtf) => new_contents.rtf = Some(rtf),
+                ClipboardContent::Image(image) => {
+                    new_contents.image = Some(
+                        image
+                            .get_dynamic_image()
+                            .map_err(|err| Error::ImageDecode(err.to_string()))?,
+                    )
+                }
+                ClipboardContent::Files(files) => new_contents.files = Some(files),
+                ClipboardContent::Other(name, bytes) => {
+                    new_contents.other.insert(name, bytes);
+                }
+            }
+        }
+        self.replace(new_contents)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.replace(MemoryContents::default())
+    }
+
+    fn watch(&self, mut on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle> {
+        let (sender, receiver) = mpsc::channel();
+        let id = {
+            let mut watchers = self.watchers.lock()?;
+            watchers.next_id += 1;
+            let id = watchers.next_id;
+            watchers.senders.push((id, sender));
+            id
+        };
+        std::thread::spawn(move || {
+            for () in receiver {
+                on_change();
+            }
+        });
+        let watchers = self.watchers.clone();
+        Ok(WatchHandle::new(move || {
+            if let Ok(mut watchers) = watchers.lock() {
+                watchers.senders.retain(|(watcher_id, _)| *watcher_id != id);
+            }
+        }))
+    }
+
+    /// Every selection is available, each with its own contents.
+    fn selection(&self, _selection: ClipboardSelection) -> Option<Box<dyn ClipboardBackend>> {
+        Some(Box::new(MemoryBackend::new()))
+    }
+}
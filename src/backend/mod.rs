@@ -0,0 +1,296 @@
+use crate::Result;
+use clipboard_rs::{ClipboardContent, ContentFormat, RustImageData};
+use serde::{Deserialize, Serialize};
+
+mod memory;
+mod system;
+mod unavailable;
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+mod wayland;
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+mod x11;
+
+pub use memory::MemoryBackend;
+pub use system::ClipboardRsBackend;
+pub(crate) use unavailable::UnavailableBackend;
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+pub use wayland::WaylandBackend;
+
+/// Which selection a call reads from or writes to.
+///
+/// Every platform has the regular clipboard. X11 also has `Primary`, the last selected text that middle-click pastes,
+/// and the rarely used `Secondary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClipboardSelection {
+    #[default]
+    Clipboard,
+    Primary,
+    Secondary,
+}
+
+impl ClipboardSelection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClipboardSelection::Clipboard => "clipboard",
+            ClipboardSelection::Primary => "primary",
+            ClipboardSelection::Secondary => "secondary",
+        }
+    }
+}
+
+/// What happens to clipboard contents written by the app when it exits, see [`crate::Builder::persist_on_exit`].
+///
+/// Only applies to X11, there the app itself serves the data it wrote and everything is gone once it quits.
+/// Windows and macOS keep the contents anyway.
+///
+/// Wayland works like X11 but has no hand-over protocol, so `WaylandBackend` ignores the policy: what the
+/// app wrote is gone when it quits, unless a clipboard manager like `wl-clip-persist` copied it in the meantime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PersistOnExit {
+    /// Leave the contents to vanish with the app.
+    Never,
+    /// Hand the contents to the running clipboard manager through the `SAVE_TARGETS` protocol.
+    #[default]
+    ClipboardManager,
+    /// Hand the contents to the clipboard manager, and without one keep serving them from a detached process
+    /// until another app takes over the clipboard or `timeout` passes.
+    ///
+    /// The detached process is the app's own executable, started again with `--tauri-plugin-clipboard-serve`.
+    /// `main` must call [`crate::serve_clipboard_if_requested`] on its first line, it serves the contents there
+    /// and exits instead of starting the app. Without that call only the clipboard manager is tried.
+    ClipboardManagerOrServe { timeout: std::time::Duration },
+}
+
+/// Platform names of the formats the typed getters already cover, and clipboard bookkeeping targets.
+/// Raw copies of these are redundant next to the typed contents.
+pub(crate) const STANDARD_FORMATS: &[&str] = &[
+    // X11 and the memory backend
+    "TARGETS",
+    "TIMESTAMP",
+    "MULTIPLE",
+    "SAVE_TARGETS",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+    "text/plain",
+    "text/plain;charset=utf-8",
+    "text/plain;charset=UTF-8",
+    "text/html",
+    "text/rtf",
+    "text/richtext",
+    "text/uri-list",
+    "image/png",
+    "x-special/gnome-copied-files",
+    "x-special/nautilus-clipboard",
+    // macOS
+    "public.utf8-plain-text",
+    "public.html",
+    "public.rtf",
+    "public.png",
+    "public.tiff",
+    "public.file-url",
+    "NSFilenamesPboardType",
+    // Windows
+    "CF_TEXT",
+    "CF_OEMTEXT",
+    "CF_UNICODETEXT",
+    "CF_LOCALE",
+    "CF_BITMAP",
+    "CF_DIB",
+    "CF_DIBV5",
+    "CF_HDROP",
+    "HTML Format",
+    "Rich Text Format",
+    "PNG",
+];
+
+/// Formats that mark clipboard contents as secret by convention, so clipboard managers
+/// and the system clipboard history skip them.
+#[cfg(target_os = "windows")]
+pub(crate) const SENSITIVE_HINTS: &[(&str, &[u8])] = &[
+    ("ExcludeClipboardContentFromMonitorProcessing", &[0]),
+    // DWORD 0, the content is kept out of Win+V history and cloud sync
+    ("CanIncludeInClipboardHistory", &[0, 0, 0, 0]),
+    ("CanUploadToCloudClipboard", &[0, 0, 0, 0]),
+];
+#[cfg(target_os = "macos")]
+pub(crate) const SENSITIVE_HINTS: &[(&str, &[u8])] = &[("org.nspasteboard.ConcealedType", &[])];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub(crate) const SENSITIVE_HINTS: &[(&str, &[u8])] = &[("x-kde-passwordManagerHint", b"secret")];
+
+/// Whether the clipboard carries one of the [`SENSITIVE_HINTS`].
+pub(crate) fn is_sensitive(clipboard: &dyn ClipboardBackend) -> bool {
+    SENSITIVE_HINTS.iter().any(|(format, _)| {
+        clipboard
+            .has(ContentFormat::Other(format.to_string()))
+            .unwrap_or(false)
+    })
+}
+
+/// Serve the clipboard and exit when this process was started to keep the app's contents after it exited,
+/// see [`crate::serve_clipboard_if_requested`].
+pub(crate) fn serve_if_requested() {
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        ))
+    ))]
+    x11::serve_if_requested();
+}
+
+/// Best effort mime type of a platform format name, `None` for bookkeeping targets and unknown names.
+pub(crate) fn normalized_mime(format: &str) -> Option<String> {
+    let mime = match format {
+        "TARGETS" | "TIMESTAMP" | "MULTIPLE" | "SAVE_TARGETS" | "CF_LOCALE" => return None,
+        "UTF8_STRING"
+        | "STRING"
+        | "TEXT"
+        | "public.utf8-plain-text"
+        | "public.utf16-plain-text"
+        | "public.plain-text"
+        | "CF_UNICODETEXT"
+        | "CF_TEXT"
+        | "CF_OEMTEXT" => "text/plain",
+        "public.html" | "HTML Format" => "text/html",
+        "public.rtf" | "Rich Text Format" | "text/richtext" => "text/rtf",
+        "public.png" | "PNG" => "image/png",
+        "public.jpeg" | "JFIF" => "image/jpeg",
+        "public.tiff" => "image/tiff",
+        "CF_DIB" | "CF_DIBV5" | "CF_BITMAP" => "image/bmp",
+        "public.file-url"
+        | "public.url"
+        | "NSFilenamesPboardType"
+        | "CF_HDROP"
+        | "x-special/gnome-copied-files"
+        | "x-special/nautilus-clipboard" => "text/uri-list",
+        // already a mime type, drop parameters like the charset
+        mime if mime.contains('/') => mime.split(';').next().unwrap_or(mime).trim(),
+        _ => return None,
+    };
+    Some(mime.to_ascii_lowercase())
+}
+
+/// The storage the plugin reads from and writes to.
+///
+/// [`ClipboardRsBackend`] talks to the system clipboard and is used by default.
+/// [`MemoryBackend`] keeps everything in process, so the plugin can run on machines without a display (e.g. CI).
+/// Select a backend with [`crate::Builder::backend`].
+pub trait ClipboardBackend: Send {
+    /// Short name reported by `backend_status`, e.g. `"clipboard-rs"`.
+    fn name(&self) -> &'static str;
+
+    /// Returns [`crate::Error::BackendUnavailable`] when the backend cannot serve any request.
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn has(&self, format: ContentFormat) -> Result<bool>;
+
+    /// Platform names of every format on the clipboard, e.g. mime types on Linux or UTIs on macOS.
+    fn available_formats(&self) -> Result<Vec<String>>;
+
+    /// Raw bytes of a format listed by [`ClipboardBackend::available_formats`].
+    fn get_buffer(&self, format: &str) -> Result<Vec<u8>>;
+
+    /// Size of a format's payload in bytes, `None` when it cannot be told without an expensive transfer.
+    fn buffer_size(&self, _format: &str) -> Option<u64> {
+        None
+    }
+
+    fn get_text(&self) -> Result<String>;
+
+    fn get_html(&self) -> Result<String>;
+
+    fn get_rich_text(&self) -> Result<String>;
+
+    fn get_image(&self) -> Result<RustImageData>;
+
+    fn get_files(&self) -> Result<Vec<String>>;
+
+    fn set_text(&self, text: String) -> Result<()>;
+
+    fn set_html(&self, html: String) -> Result<()>;
+
+    fn set_rich_text(&self, rtf: String) -> Result<()>;
+
+    fn set_image(&self, image: RustImageData) -> Result<()>;
+
+    fn set_files(&self, files: Vec<String>) -> Result<()>;
+
+    /// Replace the clipboard with all `contents` at once.
+    fn set(&self, contents: Vec<ClipboardContent>) -> Result<()>;
+
+    fn clear(&self) -> Result<()>;
+
+    /// Call `on_change` from a background thread whenever the clipboard changes, until the returned handle is stopped or dropped.
+    fn watch(&self, on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle>;
+
+    /// A backend for another selection than the regular clipboard, `None` when the platform has no such selection
+    /// or it cannot be reached.
+    /// Called once per selection when the plugin starts.
+    fn selection(&self, _selection: ClipboardSelection) -> Option<Box<dyn ClipboardBackend>> {
+        None
+    }
+
+    /// Make the contents written by the app outlive it as `policy` says. Called once when the app exits.
+    fn persist_on_exit(&self, _policy: PersistOnExit) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A running clipboard watcher started by [`ClipboardBackend::watch`].
+/// The watcher stops when this handle is stopped or dropped.
+pub struct WatchHandle {
+    stop: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl WatchHandle {
+    pub fn new(stop: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            stop: Some(Box::new(stop)),
+        }
+    }
+
+    pub fn stop(mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use super::{normalized_mime, ClipboardBackend, WatchHandle};
+use crate::{Error, Result};
+use clipboard_rs::{
+    Clipboard as ClipboardRS, ClipboardContent, ClipboardContext as ClipboardRsContext,
+    ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext, ContentFormat, RustImageData,
+};
+
+/// The system clipboard, accessed through clipboard-rs.
+pub struct ClipboardRsBackend {
+    context: ClipboardRsContext,
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        ))
+    ))]
+    keeper: Option<super::x11::ClipboardKeeper>,
+}
+
+impl ClipboardRsBackend {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            context: ClipboardRsContext::new()
+                .map_err(|err| Error::BackendUnavailable(err.to_string()))?,
+            #[cfg(all(
+                unix,
+                not(any(
+                    target_os = "macos",
+                    target_os = "android",
+                    target_os = "ios",
+                    target_os = "emscripten"
+                ))
+            ))]
+            keeper: super::x11::ClipboardKeeper::new().ok(),
+        })
+    }
+
+    /// clipboard-rs reports a missing format as a generic read error, check for it up front instead.
+    fn require(&self, format: ContentFormat, name: &str) -> Result<()> {
+        if self.context.has(format) {
+            Ok(())
+        } else {
+            Err(Error::FormatUnavailable(name.to_string()))
+        }
+    }
+
+    /// Map the result of a write, on X11 noting that the clipboard holds our data now.
+    fn written(&self, result: clipboard_rs::Result<()>) -> Result<()> {
+        result.map_err(|err| Error::Clipboard(err.to_string()))?;
+        #[cfg(all(
+            unix,
+            not(any(
+                target_os = "macos",
+                target_os = "android",
+                target_os = "ios",
+                target_os = "emscripten"
+            ))
+        ))]
+        if let Some(keeper) = &self.keeper {
+            keeper.wrote();
+        }
+        Ok(())
+    }
+}
+
+struct ChangeHandler(Box<dyn FnMut() + Send>);
+
+impl ClipboardHandler for ChangeHandler {
+    fn on_clipboard_change(&mut self) {
+        (self.0)()
+    }
+}
+
+impl ClipboardBackend for ClipboardRsBackend {
+    fn name(&self) -> &'static str {
+        "clipboard-rs"
+    }
+
+    fn has(&self, format: ContentFormat) -> Result<bool> {
+        Ok(self.context.has(format))
+    }
+
+    fn available_formats(&self) -> Result<Vec<String>> {
+        self.context
+            .available_formats()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+        self.require(ContentFormat::Other(format.to_string()), format)?;
+        self.context
+            .get_buffer(format)
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    /// Only text formats are measured, they are small and transferred quickly,
+    /// while image payloads can be many megabytes and may be rendered by the source app on request.
+    fn buffer_size(&self, format: &str) -> Option<u64> {
+        let mime = normalized_mime(format)?;
+        if !mime.starts_with("text/") {
+            return None;
+        }
+        self.context
+            .get_buffer(format)
+            .ok()
+            .map(|buffer| buffer.len() as u64)
+    }
+
+    fn get_text(&self) -> Result<String> {
+        self.require(ContentFormat::Text, "text")?;
+        self.context
+            .get_text()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.require(ContentFormat::Html, "html")?;
+        self.context
+            .get_html()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn get_rich_text(&self) -> Result<String> {
+        self.require(ContentFormat::Rtf, "rtf")?;
+        self.context
+            .get_rich_text()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn get_image(&self) -> Result<RustImageData> {
+        self.require(ContentFormat::Image, "image")?;
+        self.context
+            .get_image()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn get_files(&self) -> Result<Vec<String>> {
+        self.require(ContentFormat::Files, "files")?;
+        self.context
+            .get_files()
+            .map_err(|err| Error::Clipboard(err.to_string()))
+    }
+
+    fn set_text(&self, text: String) -> Result<()> {
+        self.written(self.context.set_text(text))
+    }
+
+    fn set_html(&self, html: String) -> Result<()> {
+        self.written(self.context.set_html(html))
+    }
+
+    fn set_rich_text(&self, rtf: String) -> Result<()> {
+        self.written(self.context.set_rich_text(rtf))
+    }
+
+    fn set_image(&self, image: RustImageData) -> Result<()> {
+        self.written(self.context.set_image(image))
+    }
+
+    fn set_files(&self, files: Vec<String>) -> Result<()> {
+        self.written(self.context.set_files(files))
+    }
+
+    fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        self.written(self.context.set(contents))
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.written(self.context.clear())
+    }
+
+    fn watch(&self, on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle> {
+        let mut watcher: ClipboardWatcherContext<ChangeHandler> =
+            ClipboardWatcherContext::new().map_err(|err| Error::Clipboard(err.to_string()))?;
+        let shutdown = watcher
+            .add_handler(ChangeHandler(on_change))
+            .get_shutdown_channel();
+        std::thread::spawn(move || {
+            watcher.start_watch();
+        });
+        Ok(WatchHandle::new(move || shutdown.stop()))
+    }
+
+    /// clipboard-rs only covers the regular clipboard, the X11 selections get their own connection.
+    /// Selections whose connection fails are left out, so they are not listed as available.
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        ))
+    ))]
+    fn selection(&self, selection: super::ClipboardSelection) -> Option<Box<dyn ClipboardBackend>> {
+        use super::{x11::X11SelectionBackend, ClipboardSelection};
+        if selection == ClipboardSelection::Clipboard {
+            return None;
+        }
+        match X11SelectionBackend::new(selection) {
+            Ok(backend) => Some(Box::new(backend)),
+            Err(err) => {
+                log::warn!("the {} selection is unavailable: {err}", selection.name());
+                None
+            }
+        }
+    }
+
+    /// The X11 clipboard is served by the app itself, its contents are handed over before the app is gone.
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        ))
+    ))]
+    fn persist_on_exit(&self, policy: super::PersistOnExit) -> Result<()> {
+        match &self.keeper {
+            Some(keeper) => keeper.persist(policy),
+            None => Ok(()),
+        }
+    }
+}
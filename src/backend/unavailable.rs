@@ -0,0 +1,94 @@
+use super::{ClipboardBackend, WatchHandle};
+use crate::{Error, Result};
+use clipboard_rs::{ClipboardContent, ContentFormat, RustImageData};
+
+/// Stands in for a backend that failed to initialize (e.g. no X server), so the plugin
+/// keeps the app running and every call reports why the clipboard is not usable.
+pub(crate) struct UnavailableBackend {
+    name: &'static str,
+    reason: String,
+}
+
+impl UnavailableBackend {
+    pub fn new(name: &'static str, reason: String) -> Self {
+        Self { name, reason }
+    }
+
+    fn error<T>(&self) -> Result<T> {
+        Err(Error::BackendUnavailable(self.reason.clone()))
+    }
+}
+
+impl ClipboardBackend for UnavailableBackend {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn check(&self) -> Result<()> {
+        self.error()
+    }
+
+    fn has(&self, _format: ContentFormat) -> Result<bool> {
+        self.error()
+    }
+
+    fn available_formats(&self) -> Result<Vec<String>> {
+        self.error()
+    }
+
+    fn get_buffer(&self, _format: &str) -> Result<Vec<u8>> {
+        self.error()
+    }
+
+    fn get_text(&self) -> Result<String> {
+        self.error()
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.error()
+    }
+
+    fn get_rich_text(&self) -> Result<String> {
+        self.error()
+    }
+
+    fn get_image(&self) -> Result<RustImageData> {
+        self.error()
+    }
+
+    fn get_files(&self) -> Result<Vec<String>> {
+        self.error()
+    }
+
+    fn set_text(&self, _text: String) -> Result<()> {
+        self.error()
+    }
+
+    fn set_html(&self, _html: String) -> Result<()> {
+        self.error()
+    }
+
+    fn set_rich_text(&self, _rtf: String) -> Result<()> {
+        self.error()
+    }
+
+    fn set_image(&self, _image: RustImageData) -> Result<()> {
+        self.error()
+    }
+
+    fn set_files(&self, _files: Vec<String>) -> Result<()> {
+        self.error()
+    }
+
+    fn set(&self, _contents: Vec<ClipboardContent>) -> Result<()> {
+        self.error()
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.error()
+    }
+
+    fn watch(&self, _on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle> {
+        self.error()
+    }
+}
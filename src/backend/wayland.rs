@@ -0,0 +1,784 @@
+use super::{normalized_mime, ClipboardBackend, ClipboardSelection, WatchHandle};
+use crate::{Error, Result};
+use clipboard_rs::{common::RustImage, ClipboardContent, ContentFormat, RustImageData};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Sender, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use wayland_client::{
+    backend::ObjectId,
+    event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_callback::WlCallback, wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+
+mod wlr {
+    pub use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self as device, ZwlrDataControlDeviceV1 as Device},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1 as Manager,
+        zwlr_data_control_offer_v1::{self as offer, ZwlrDataControlOfferV1 as Offer},
+        zwlr_data_control_source_v1::{self as source, ZwlrDataControlSourceV1 as Source},
+    };
+}
+
+mod ext {
+    pub use wayland_protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self as device, ExtDataControlDeviceV1 as Device},
+        ext_data_control_manager_v1::ExtDataControlManagerV1 as Manager,
+        ext_data_control_offer_v1::{self as offer, ExtDataControlOfferV1 as Offer},
+        ext_data_control_source_v1::{self as source, ExtDataControlSourceV1 as Source},
+    };
+}
+
+/// how long a read waits for the next chunk from the app offering the data
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+/// how long a write waits for the compositor to confirm the new selection by default
+const SYNC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Text mime types, in order of preference. `STRING` is latin-1.
+const TEXT_MIMES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+fn wayland_error(err: impl std::fmt::Display) -> Error {
+    Error::Clipboard(err.to_string())
+}
+
+fn unavailable(err: impl std::fmt::Display) -> Error {
+    Error::BackendUnavailable(err.to_string())
+}
+
+/// The data-control manager and the device of the seat, from whichever protocol the compositor offers.
+/// ext-data-control is the standardized successor of wlr-data-control, both work alike.
+enum DataControl {
+    Ext(ext::Manager, ext::Device),
+    Wlr(wlr::Manager, wlr::Device),
+}
+
+enum Source {
+    Ext(ext::Source),
+    Wlr(wlr::Source),
+}
+
+#[derive(Clone)]
+enum Offer {
+    Ext(ext::Offer),
+    Wlr(wlr::Offer),
+}
+
+impl DataControl {
+    fn create_source(&self, qh: &QueueHandle<State>, mimes: &[String]) -> Source {
+        match self {
+            DataControl::Ext(manager, _) => {
+                let source = manager.create_data_source(qh, ());
+                mimes.iter().for_each(|mime| source.offer(mime.clone()));
+                Source::Ext(source)
+            }
+            DataControl::Wlr(manager, _) => {
+                let source = manager.create_data_source(qh, ());
+                mimes.iter().for_each(|mime| source.offer(mime.clone()));
+                Source::Wlr(source)
+            }
+        }
+    }
+
+    /// Offer `source` on `selection`, `None` clears the selection.
+    fn set_selection(&self, selection: ClipboardSelection, source: Option<&Source>) {
+        match self {
+            DataControl::Ext(_, device) => {
+                let source = source.and_then(|source| match source {
+                    Source::Ext(source) => Some(source),
+                    Source::Wlr(_) => None,
+                });
+                if selection == ClipboardSelection::Primary {
+                    device.set_primary_selection(source);
+                } else {
+                    device.set_selection(source);
+                }
+            }
+            DataControl::Wlr(_, device) => {
+                let source = source.and_then(|source| match source {
+                    Source::Wlr(source) => Some(source),
+                    Source::Ext(_) => None,
+                });
+                if selection == ClipboardSelection::Primary {
+                    device.set_primary_selection(source);
+                } else {
+                    device.set_selection(source);
+                }
+            }
+        }
+    }
+
+    /// The primary selection came with version 2 of wlr-data-control and is part of ext-data-control.
+    fn has_primary(&self) -> bool {
+        match self {
+            DataControl::Ext(..) => true,
+            DataControl::Wlr(manager, _) => manager.version() >= 2,
+        }
+    }
+}
+
+impl Source {
+    fn id(&self) -> ObjectId {
+        match self {
+            Source::Ext(source) => source.id(),
+            Source::Wlr(source) => source.id(),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Source::Ext(source) => source.destroy(),
+            Source::Wlr(source) => source.destroy(),
+        }
+    }
+}
+
+impl Offer {
+    fn id(&self) -> ObjectId {
+        match self {
+            Offer::Ext(offer) => offer.id(),
+            Offer::Wlr(offer) => offer.id(),
+        }
+    }
+
+    /// Ask the app offering the data to write it as `mime` into `fd`.
+    fn receive(&self, mime: &str, fd: &OwnedFd) {
+        match self {
+            Offer::Ext(offer) => offer.receive(mime.to_string(), fd.as_fd()),
+            Offer::Wlr(offer) => offer.receive(mime.to_string(), fd.as_fd()),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Offer::Ext(offer) => offer.destroy(),
+            Offer::Wlr(offer) => offer.destroy(),
+        }
+    }
+}
+
+/// Data one of our sources offers, by mime type.
+type SourceData = Arc<Vec<(String, Vec<u8>)>>;
+
+/// What the compositor currently offers on one selection, and who listens for changes.
+#[derive(Default)]
+struct SelectionState {
+    offer: Option<(Offer, Vec<String>)>,
+    watchers: Vec<(u64, Sender<()>)>,
+}
+
+/// State shared between the backend and the thread dispatching Wayland events.
+#[derive(Default)]
+struct Shared {
+    /// mime types of offers that did not become a selection yet
+    pending: HashMap<ObjectId, Vec<String>>,
+    clipboard: SelectionState,
+    primary: SelectionState,
+    /// our sources, until the compositor cancels them
+    sources: HashMap<ObjectId, (Source, SourceData)>,
+    /// set once the compositor invalidated the device, e.g. because the seat went away
+    finished: bool,
+}
+
+impl Shared {
+    fn selection(&mut self, selection: ClipboardSelection) -> &mut SelectionState {
+        if selection == ClipboardSelection::Primary {
+            &mut self.primary
+        } else {
+            &mut self.clipboard
+        }
+    }
+
+    fn offer_changed(&mut self, selection: ClipboardSelection, offer: Option<Offer>) {
+        let offer = offer.map(|offer| {
+            let mimes = self.pending.remove(&offer.id()).unwrap_or_default();
+            (offer, mimes)
+        });
+        let state = self.selection(selection);
+        if let Some((previous, _)) = std::mem::replace(&mut state.offer, offer) {
+            previous.destroy();
+        }
+        state
+            .watchers
+            .retain(|(_, watcher)| watcher.send(()).is_ok());
+    }
+}
+
+struct State {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl State {
+    fn shared(&self) -> std::sync::MutexGuard<'_, Shared> {
+        // a panicking watcher must not stop event dispatching
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Write our data for `mime` into `fd` from another thread, the reader may be slow.
+    fn send(&self, source: ObjectId, mime: String, fd: OwnedFd) {
+        let Some(data) = self
+            .shared()
+            .sources
+            .get(&source)
+            .map(|(_, data)| data.clone())
+        else {
+            return;
+        };
+        thread::spawn(move || {
+            if let Some((_, bytes)) = data.iter().find(|(offered, _)| *offered == mime) {
+                let _ = File::from(fd).write_all(bytes);
+            }
+        });
+    }
+
+    fn cancelled(&self, source: ObjectId) {
+        if let Some((source, _)) = self.shared().sources.remove(&source) {
+            source.destroy();
+        }
+    }
+}
+
+macro_rules! dispatch_data_control {
+    ($protocol:ident, $variant:ident) => {
+        impl Dispatch<$protocol::Manager, ()> for State {
+            fn event(
+                _: &mut Self,
+                _: &$protocol::Manager,
+                _: <$protocol::Manager as Proxy>::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+            }
+        }
+
+        impl Dispatch<$protocol::Device, ()> for State {
+            fn event(
+                state: &mut Self,
+                _: &$protocol::Device,
+                event: $protocol::device::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                let mut shared = state.shared();
+                match event {
+                    $protocol::device::Event::DataOffer { id } => {
+                        shared.pending.insert(id.id(), vec![]);
+                    }
+                    $protocol::device::Event::Selection { id } => {
+                        shared.offer_changed(ClipboardSelection::Clipboard, id.map(Offer::$variant))
+                    }
+                    $protocol::device::Event::PrimarySelection { id } => {
+                        shared.offer_changed(ClipboardSelection::Primary, id.map(Offer::$variant))
+                    }
+                    $protocol::device::Event::Finished => shared.finished = true,
+                    _ => {}
+                }
+            }
+
+            event_created_child!(State, $protocol::Device, [
+                $protocol::device::EVT_DATA_OFFER_OPCODE => ($protocol::Offer, ()),
+            ]);
+        }
+
+        impl Dispatch<$protocol::Offer, ()> for State {
+            fn event(
+                state: &mut Self,
+                offer: &$protocol::Offer,
+                event: $protocol::offer::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                if let $protocol::offer::Event::Offer { mime_type } = event {
+                    if let Some(mimes) = state.shared().pending.get_mut(&offer.id()) {
+                        mimes.push(mime_type);
+                    }
+                }
+            }
+        }
+
+        impl Dispatch<$protocol::Source, ()> for State {
+            fn event(
+                state: &mut Self,
+                source: &$protocol::Source,
+                event: $protocol::source::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $protocol::source::Event::Send { mime_type, fd } => {
+                        state.send(source.id(), mime_type, fd)
+                    }
+                    $protocol::source::Event::Cancelled => state.cancelled(source.id()),
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+dispatch_data_control!(ext, Ext);
+dispatch_data_control!(wlr, Wlr);
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Callbacks of `wl_display.sync`, telling that the compositor handled every earlier request.
+impl Dispatch<WlCallback, Option<SyncSender<()>>> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlCallback,
+        _: <WlCallback as Proxy>::Event,
+        done: &Option<SyncSender<()>>,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let Some(done) = done {
+            let _ = done.try_send(());
+        }
+    }
+}
+
+/// The connection and objects shared by the backends of all selections.
+struct Inner {
+    conn: Connection,
+    qh: QueueHandle<State>,
+    control: DataControl,
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    next_watcher: AtomicU64,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // wake the dispatching thread so it sees the stop flag
+        self.stop.store(true, Ordering::SeqCst);
+        self.conn.display().sync(&self.qh, None);
+        let _ = self.conn.flush();
+    }
+}
+
+/// The clipboard of a Wayland session, through the data-control protocol (ext-data-control or wlr-data-control).
+///
+/// Unlike the X11 clipboard that clipboard-rs uses through XWayland, it sees every change made by native Wayland apps.
+/// Picked automatically when `WAYLAND_DISPLAY` is set and the compositor supports data-control, e.g. on wlroots
+/// based compositors and KDE. [`WaylandBackend::connect`] targets a specific display, e.g. a headless compositor in tests.
+pub struct WaylandBackend {
+    selection: ClipboardSelection,
+    sync_timeout: Duration,
+    inner: Arc<Inner>,
+}
+
+impl WaylandBackend {
+    /// Connect to the compositor named by `WAYLAND_DISPLAY`.
+    pub fn new() -> Result<Self> {
+        Self::from_connection(Connection::connect_to_env().map_err(unavailable)?)
+    }
+
+    /// Connect to the compositor listening on `display`, a socket name in `XDG_RUNTIME_DIR` like `wayland-1` or an absolute path.
+    pub fn connect(display: &str) -> Result<Self> {
+        let mut path = PathBuf::from(display);
+        if path.is_relative() {
+            let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+                .ok_or_else(|| unavailable("XDG_RUNTIME_DIR is not set"))?;
+            path = PathBuf::from(runtime_dir).join(display);
+        }
+        let stream = UnixStream::connect(path).map_err(unavailable)?;
+        Self::from_connection(Connection::from_socket(stream).map_err(unavailable)?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self> {
+        let (globals, mut queue): (_, EventQueue<State>) =
+            registry_queue_init(&conn).map_err(unavailable)?;
+        let qh = queue.handle();
+        let seat: WlSeat = globals.bind(&qh, 1..=1, ()).map_err(unavailable)?;
+        let control = if let Ok(manager) = globals.bind::<ext::Manager, _, _>(&qh, 1..=1, ()) {
+            let device = manager.get_data_device(&seat, &qh, ());
+            DataControl::Ext(manager, device)
+        } else if let Ok(manager) = globals.bind::<wlr::Manager, _, _>(&qh, 1..=2, ()) {
+            let device = manager.get_data_device(&seat, &qh, ());
+            DataControl::Wlr(manager, device)
+        } else {
+            return Err(unavailable(
+                "the compositor supports neither ext-data-control nor wlr-data-control",
+            ));
+        };
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let mut state = State {
+            shared: shared.clone(),
+        };
+        // the device announces the current selections right away
+        queue.roundtrip(&mut state).map_err(unavailable)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) && queue.blocking_dispatch(&mut state).is_ok() {}
+            });
+        }
+        Ok(Self {
+            selection: ClipboardSelection::Clipboard,
+            sync_timeout: SYNC_TIMEOUT,
+            inner: Arc::new(Inner {
+                conn,
+                qh,
+                control,
+                shared,
+                stop,
+                next_watcher: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// How long a write waits for the compositor to confirm the new selection, 2 seconds by default.
+    /// A busy compositor that takes longer fails the write with [`Error::Timeout`], the write can be retried.
+    pub fn sync_timeout(mut self, timeout: Duration) -> Self {
+        self.sync_timeout = timeout;
+        self
+    }
+
+    /// Wait until the compositor handled every request sent so far and we got its answers,
+    /// so a read right after a write sees the new selection.
+    fn sync(&self) -> Result<()> {
+        let (done, synced) = mpsc::sync_channel(1);
+        self.inner.conn.display().sync(&self.inner.qh, Some(done));
+        self.inner.conn.flush().map_err(wayland_error)?;
+        synced
+            .recv_timeout(self.sync_timeout)
+            .map_err(|_| Error::Timeout("the compositor to confirm the selection".to_string()))
+    }
+
+    /// The current offer and its mime types, `None` when the selection is empty.
+    fn offer(&self) -> Result<Option<(Offer, Vec<String>)>> {
+        let mut shared = self.inner.shared.lock()?;
+        if shared.finished {
+            return Err(unavailable(
+                "the compositor invalidated the data-control device",
+            ));
+        }
+        Ok(shared.selection(self.selection).offer.clone())
+    }
+
+    fn mimes(&self) -> Result<Vec<String>> {
+        Ok(self.offer()?.map(|(_, mimes)| mimes).unwrap_or_default())
+    }
+
+    /// Read the selection as `mime`.
+    fn read(&self, offer: &Offer, mime: &str) -> Result<Vec<u8>> {
+        let (read, write) = pipe()?;
+        offer.receive(mime, &write);
+        // the offering app writes into its own copy of the pipe
+        drop(write);
+        self.inner.conn.flush().map_err(wayland_error)?;
+        read_to_end(read)
+    }
+
+    /// Read the first of `mimes` the selection offers.
+    fn read_first(&self, mimes: &[&str], name: &str) -> Result<(String, Vec<u8>)> {
+        let (offer, offered) = self
+            .offer()?
+            .ok_or_else(|| Error::FormatUnavailable(name.to_string()))?;
+        let mime = mimes
+            .iter()
+            .find(|mime| offered.iter().any(|offered| offered == *mime))
+            .ok_or_else(|| Error::FormatUnavailable(name.to_string()))?;
+        Ok((mime.to_string(), self.read(&offer, mime)?))
+    }
+
+    fn read_string(&self, mimes: &[&str], name: &str) -> Result<String> {
+        let (mime, data) = self.read_first(mimes, name)?;
+        Ok(if mime == "STRING" {
+            data.into_iter().map(char::from).collect()
+        } else {
+            String::from_utf8_lossy(&data).into_owned()
+        })
+    }
+
+    /// Offer `data` on the selection.
+    fn own(&self, data: Vec<(String, Vec<u8>)>) -> Result<()> {
+        let mimes: Vec<String> = data.iter().map(|(mime, _)| mime.clone()).collect();
+        let source = self.inner.control.create_source(&self.inner.qh, &mimes);
+        // hold the lock until the data is stored, requests for it are answered by the dispatching thread
+        let mut shared = self.inner.shared.lock()?;
+        self.inner
+            .control
+            .set_selection(self.selection, Some(&source));
+        shared.sources.insert(source.id(), (source, Arc::new(data)));
+        drop(shared);
+        self.sync()
+    }
+}
+
+/// Mime types and data offered for `content`.
+fn encode(content: ClipboardContent) -> Result<Vec<(String, Vec<u8>)>> {
+    Ok(match content {
+        ClipboardContent::Text(text) => TEXT_MIMES
+            .iter()
+            .map(|mime| (mime.to_string(), text.clone().into_bytes()))
+            .collect(),
+        ClipboardContent::Html(html) => vec![("text/html".to_string(), html.into_bytes())],
+        ClipboardContent::Rtf(rtf) => vec![("text/rtf".to_string(), rtf.into_bytes())],
+        ClipboardContent::Image(image) => {
+            let png = image
+                .to_png()
+                .map_err(|err| Error::ImageEncode(err.to_string()))?;
+            vec![("image/png".to_string(), png.get_bytes().to_vec())]
+        }
+        ClipboardContent::Files(files) => {
+            let paths: Vec<&str> = files
+                .iter()
+                .map(|file| file.strip_prefix("file://").unwrap_or(file))
+                .collect();
+            vec![
+                ("text/uri-list".to_string(), files.join("\r\n").into_bytes()),
+                (
+                    "x-special/gnome-copied-files".to_string(),
+                    format!("copy\n{}", files.join("\n")).into_bytes(),
+                ),
+                (
+                    "text/plain;charset=utf-8".to_string(),
+                    paths.join("\n").into_bytes(),
+                ),
+            ]
+        }
+        ClipboardContent::Other(format, data) => vec![(format, data)],
+    })
+}
+
+fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: pipe2 fills both descriptors on success, each is owned exactly once below
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(Error::Io(std::io::Error::last_os_error()));
+    }
+    // SAFETY: see above
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Read until the writer closes the pipe, giving up when it stays silent for [`READ_TIMEOUT`].
+fn read_to_end(fd: OwnedFd) -> Result<Vec<u8>> {
+    let mut file = File::from(fd);
+    let mut data = vec![];
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        let mut poll = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: polls the one descriptor we own
+        let ready = unsafe { libc::poll(&mut poll, 1, READ_TIMEOUT.as_millis() as i32) };
+        if ready < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(Error::Io(err));
+        }
+        if ready == 0 {
+            return Err(Error::Timeout("the app offering the data".to_string()));
+        }
+        match file.read(&mut chunk) {
+            Ok(0) => return Ok(data),
+            Ok(read) => data.extend_from_slice(&chunk[..read]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(Error::Io(err)),
+        }
+    }
+}
+
+/// Mime types that carry `format`, in order of preference.
+fn format_mimes(format: &ContentFormat) -> Vec<&str> {
+    match format {
+        ContentFormat::Text => TEXT_MIMES.to_vec(),
+        ContentFormat::Html => vec!["text/html"],
+        ContentFormat::Rtf => vec!["text/rtf", "application/rtf"],
+        ContentFormat::Image => vec!["image/png"],
+        ContentFormat::Files => vec!["text/uri-list"],
+        ContentFormat::Other(name) => vec![name.as_str()],
+    }
+}
+
+impl ClipboardBackend for WaylandBackend {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn check(&self) -> Result<()> {
+        self.offer().map(|_| ())
+    }
+
+    fn has(&self, format: ContentFormat) -> Result<bool> {
+        let offered = self.mimes()?;
+        Ok(format_mimes(&format)
+            .iter()
+            .any(|mime| offered.iter().any(|offered| offered == mime)))
+    }
+
+    fn available_formats(&self) -> Result<Vec<String>> {
+        self.mimes()
+    }
+
+    fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+        self.read_first(&[format], format).map(|(_, data)| data)
+    }
+
+    /// Only text formats are measured, like on X11.
+    fn buffer_size(&self, format: &str) -> Option<u64> {
+        if !normalized_mime(format)?.starts_with("text/") {
+            return None;
+        }
+        self.get_buffer(format)
+            .ok()
+            .map(|buffer| buffer.len() as u64)
+    }
+
+    fn get_text(&self) -> Result<String> {
+        self.read_string(TEXT_MIMES, "text")
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.read_string(&format_mimes(&ContentFormat::Html), "html")
+    }
+
+    fn get_rich_text(&self) -> Result<String> {
+        self.read_string(&format_mimes(&ContentFormat::Rtf), "rtf")
+    }
+
+    fn get_image(&self) -> Result<RustImageData> {
+        let (_, png) = self.read_first(&format_mimes(&ContentFormat::Image), "image")?;
+        RustImageData::from_bytes(&png).map_err(|err| Error::ImageDecode(err.to_string()))
+    }
+
+    fn get_files(&self) -> Result<Vec<String>> {
+        let uris = self.read_string(&format_mimes(&ContentFormat::Files), "files")?;
+        Ok(uris
+            .lines()
+            .map(str::trim)
+            .filter(|uri| uri.starts_with("file://"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn set_text(&self, text: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Text(text)])
+    }
+
+    fn set_html(&self, html: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Html(html)])
+    }
+
+    fn set_rich_text(&self, rtf: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Rtf(rtf)])
+    }
+
+    fn set_image(&self, image: RustImageData) -> Result<()> {
+        self.set(vec![ClipboardContent::Image(image)])
+    }
+
+    fn set_files(&self, files: Vec<String>) -> Result<()> {
+        self.set(vec![ClipboardContent::Files(files)])
+    }
+
+    fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        let mut data: Vec<(String, Vec<u8>)> = vec![];
+        for content in contents {
+            for (mime, bytes) in encode(content)? {
+                // the first content offering a mime type wins, e.g. text over the paths of files
+                if !data.iter().any(|(existing, _)| *existing == mime) {
+                    data.push((mime, bytes));
+                }
+            }
+        }
+        self.own(data)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.inner.control.set_selection(self.selection, None);
+        self.sync()
+    }
+
+    fn watch(&self, mut on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle> {
+        let id = self.inner.next_watcher.fetch_add(1, Ordering::SeqCst);
+        let (changed, changes) = mpsc::channel();
+        self.inner
+            .shared
+            .lock()?
+            .selection(self.selection)
+            .watchers
+            .push((id, changed));
+        // reads from `on_change` need the dispatching thread to deliver the data, so it runs elsewhere
+        thread::spawn(move || {
+            while changes.recv().is_ok() {
+                on_change();
+            }
+        });
+        let (shared, selection) = (self.inner.shared.clone(), self.selection);
+        Ok(WatchHandle::new(move || {
+            if let Ok(mut shared) = shared.lock() {
+                shared
+                    .selection(selection)
+                    .watchers
+                    .retain(|(watcher, _)| *watcher != id);
+            }
+        }))
+    }
+
+    /// The primary selection shares the connection, Wayland has no secondary selection.
+    fn selection(&self, selection: ClipboardSelection) -> Option<Box<dyn ClipboardBackend>> {
+        if selection != ClipboardSelection::Primary || !self.inner.control.has_primary() {
+            return None;
+        }
+        Some(Box::new(WaylandBackend {
+            selection,
+            sync_timeout: self.sync_timeout,
+            inner: self.inner.clone(),
+        }))
+    }
+}
@@ -0,0 +1,924 @@
+use super::{normalized_mime, ClipboardBackend, ClipboardSelection, PersistOnExit, WatchHandle};
+use crate::{Error, Result};
+use clipboard_rs::{common::RustImage, ClipboardContent, ContentFormat, RustImageData};
+use std::{
+    cell::Cell,
+    fmt::Display,
+    io::{Read, Write},
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux,
+            EventMask, PropMode, Property, PropertyNotifyEvent, SelectionNotifyEvent,
+            SelectionRequestEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+x11rb::atom_manager! {
+    Atoms: AtomCookies {
+        CLIPBOARD,
+        CLIPBOARD_MANAGER,
+        TARGETS,
+        TIMESTAMP,
+        MULTIPLE,
+        SAVE_TARGETS,
+        INCR,
+        // property the selection owner puts the data in for us
+        TRANSFER: b"TAURI_PLUGIN_CLIPBOARD_TRANSFER",
+        UTF8_STRING,
+        STRING,
+        TEXT_PLAIN: b"text/plain",
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        HTML: b"text/html",
+        RTF: b"text/rtf",
+        PNG: b"image/png",
+        URI_LIST: b"text/uri-list",
+        GNOME_FILES: b"x-special/gnome-copied-files",
+    }
+}
+
+/// how long a read waits for the selection owner to answer, or for the next chunk of a large transfer
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+/// how long the clipboard manager may take to copy every target at exit
+const SAVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// how long a requestor may take to ask for the next chunk of a large answer before the transfer is dropped
+const INCR_TIMEOUT: Duration = Duration::from_secs(5);
+/// largest chunk of a large answer
+const INCR_CHUNK: usize = 64 * 1024;
+/// first argument of the app's executable when it is started again to keep serving the clipboard after the app
+/// exited, followed by the timeout in milliseconds
+const SERVE_ARG: &str = "--tauri-plugin-clipboard-serve";
+
+/// set once `main` called [`crate::serve_clipboard_if_requested`], only then does a started helper serve instead
+/// of running the app a second time
+static SERVE_ENTRY_POINT: AtomicBool = AtomicBool::new(false);
+
+fn x11_error(err: impl Display) -> Error {
+    Error::Clipboard(err.to_string())
+}
+
+/// A connection to the X server with a hidden window to send and receive selection data on.
+struct X11Connection {
+    conn: RustConnection,
+    window: Window,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl X11Connection {
+    fn open() -> Result<Self> {
+        let (conn, screen) =
+            x11rb::connect(None).map_err(|err| Error::BackendUnavailable(err.to_string()))?;
+        let screen = &conn.setup().roots[screen];
+        let (root, visual) = (screen.root, screen.root_visual);
+        let window = conn.generate_id().map_err(x11_error)?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &CreateWindowAux::new()
+                .event_mask(EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(x11_error)?
+        .check()
+        .map_err(x11_error)?;
+        let atoms = Atoms::new(&conn)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        Ok(Self {
+            conn,
+            window,
+            root,
+            atoms,
+        })
+    }
+
+    fn atom(&self, name: &str) -> Result<Atom> {
+        Ok(self
+            .conn
+            .intern_atom(false, name.as_bytes())
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .atom)
+    }
+
+    fn atom_name(&self, atom: Atom) -> Result<String> {
+        let reply = self
+            .conn
+            .get_atom_name(atom)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        Ok(String::from_utf8_lossy(&reply.name).into_owned())
+    }
+
+    /// The next event, `None` once `deadline` passed without one. Blocks on the connection until it is readable.
+    fn next_event(&self, deadline: Instant) -> Result<Option<Event>> {
+        loop {
+            if let Some(event) = self.conn.poll_for_event().map_err(x11_error)? {
+                return Ok(Some(event));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            // requests still in the write buffer may be what the answer waits on
+            self.conn.flush().map_err(x11_error)?;
+            let mut fd = libc::pollfd {
+                fd: self.conn.stream().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // rounded up, so the last wait does not spin until the deadline
+            let timeout = (deadline - now).as_millis().clamp(1, i32::MAX as u128) as i32;
+            // SAFETY: `fd` is a single valid pollfd for the duration of the call
+            if unsafe { libc::poll(&mut fd, 1, timeout) } < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+
+    /// Current owner window of `selection`, `NONE` when nobody owns it.
+    fn selection_owner(&self, selection: Atom) -> Result<Window> {
+        Ok(self
+            .conn
+            .get_selection_owner(selection)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .owner)
+    }
+
+    /// Make our window the owner of `selection`.
+    fn own(&self, selection: Atom) -> Result<()> {
+        self.conn
+            .set_selection_owner(self.window, selection, CURRENT_TIME)
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+        if self.selection_owner(selection)? != self.window {
+            return Err(Error::Clipboard(
+                "failed to take ownership of the selection".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ask the selection owner to convert its data to `target` and wait for the answer.
+    /// `timeout` bounds the wait for the answer and for every chunk of a large transfer.
+    fn read(&self, selection: Atom, target: Atom, timeout: Duration) -> Result<Vec<u8>> {
+        let (conn, atoms) = (&self.conn, &self.atoms);
+        conn.convert_selection(self.window, selection, target, atoms.TRANSFER, CURRENT_TIME)
+            .map_err(x11_error)?;
+        conn.flush().map_err(x11_error)?;
+        let mut deadline = Instant::now() + timeout;
+        let mut incremental: Option<Vec<u8>> = None;
+        loop {
+            let Some(event) = self.next_event(deadline)? else {
+                return Err(Error::Clipboard(
+                    "timed out waiting for the selection owner".to_string(),
+                ));
+            };
+            match event {
+                Event::SelectionNotify(notify)
+                    if incremental.is_none()
+                        && notify.selection == selection
+                        && notify.target == target =>
+                {
+                    if notify.property == NONE {
+                        return Err(Error::FormatUnavailable(
+                            self.atom_name(target).unwrap_or_default(),
+                        ));
+                    }
+                    let (type_, data) = self.take_transfer()?;
+                    if type_ != atoms.INCR {
+                        return Ok(data);
+                    }
+                    // deleting the INCR property asked the owner for the first chunk
+                    incremental = Some(vec![]);
+                    deadline = Instant::now() + timeout;
+                }
+                Event::PropertyNotify(notify)
+                    if notify.window == self.window
+                        && notify.atom == atoms.TRANSFER
+                        && notify.state == Property::NEW_VALUE =>
+                {
+                    let Some(data) = incremental.as_mut() else {
+                        continue;
+                    };
+                    let (_, chunk) = self.take_transfer()?;
+                    if chunk.is_empty() {
+                        return Ok(std::mem::take(data));
+                    }
+                    data.extend_from_slice(&chunk);
+                    deadline = Instant::now() + timeout;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Type and value of the transfer property, deleting it as the protocol requires.
+    fn take_transfer(&self) -> Result<(Atom, Vec<u8>)> {
+        let reply = self
+            .conn
+            .get_property(
+                true,
+                self.window,
+                self.atoms.TRANSFER,
+                AtomEnum::ANY,
+                0,
+                u32::MAX,
+            )
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        Ok((reply.type_, reply.value))
+    }
+
+    /// Targets offered by the current selection owner, empty when nobody owns the selection.
+    fn targets(&self, selection: Atom) -> Result<Vec<Atom>> {
+        match self.read(selection, self.atoms.TARGETS, READ_TIMEOUT) {
+            Ok(data) => Ok(data
+                .chunks_exact(4)
+                .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect()),
+            Err(Error::FormatUnavailable(_)) => Ok(vec![]),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Data this backend offers while it owns the selection, by target.
+type OwnedData = Arc<Mutex<Vec<(Atom, Vec<u8>)>>>;
+
+/// An X11 selection that clipboard-rs does not expose, e.g. PRIMARY for middle-click paste.
+///
+/// Reads use their own connection. Written data stays in memory and is handed out by a background thread
+/// until another app takes over the selection, like X11 apps do.
+pub(crate) struct X11SelectionBackend {
+    selection: Atom,
+    reader: X11Connection,
+    owner: Arc<X11Connection>,
+    owned: OwnedData,
+}
+
+impl X11SelectionBackend {
+    pub fn new(selection: ClipboardSelection) -> Result<Self> {
+        let reader = X11Connection::open()?;
+        let owner = Arc::new(X11Connection::open()?);
+        // the selection atoms are named like the variants, e.g. PRIMARY
+        let selection = reader.atom(&selection.name().to_ascii_uppercase())?;
+        let owned = OwnedData::default();
+        {
+            let (owner, owned) = (owner.clone(), owned.clone());
+            thread::spawn(move || serve(&owner, selection, &owned));
+        }
+        Ok(Self {
+            selection,
+            reader,
+            owner,
+            owned,
+        })
+    }
+
+    /// Targets that carry `format`, in order of preference.
+    fn format_targets(&self, format: &ContentFormat) -> Result<Vec<Atom>> {
+        let atoms = &self.reader.atoms;
+        Ok(match format {
+            ContentFormat::Text => vec![
+                atoms.UTF8_STRING,
+                atoms.TEXT_PLAIN_UTF8,
+                atoms.TEXT_PLAIN,
+                atoms.STRING,
+            ],
+            ContentFormat::Html => vec![atoms.HTML],
+            ContentFormat::Rtf => vec![atoms.RTF],
+            ContentFormat::Image => vec![atoms.PNG],
+            ContentFormat::Files => vec![atoms.URI_LIST],
+            ContentFormat::Other(name) => vec![self.reader.atom(name)?],
+        })
+    }
+
+    /// Read the first target of `format` the owner offers.
+    fn read_format(&self, format: ContentFormat, name: &str) -> Result<(Atom, Vec<u8>)> {
+        let targets = self.reader.targets(self.selection)?;
+        let target = self
+            .format_targets(&format)?
+            .into_iter()
+            .find(|target| targets.contains(target))
+            .ok_or_else(|| Error::FormatUnavailable(name.to_string()))?;
+        Ok((
+            target,
+            self.reader.read(self.selection, target, READ_TIMEOUT)?,
+        ))
+    }
+
+    fn read_string(&self, format: ContentFormat, name: &str) -> Result<String> {
+        let (target, data) = self.read_format(format, name)?;
+        Ok(if target == self.reader.atoms.STRING {
+            // STRING is latin-1
+            data.into_iter().map(char::from).collect()
+        } else {
+            String::from_utf8_lossy(&data).into_owned()
+        })
+    }
+
+    /// Targets and data offered for `content`.
+    fn encode(&self, content: ClipboardContent) -> Result<Vec<(Atom, Vec<u8>)>> {
+        let atoms = &self.owner.atoms;
+        Ok(match content {
+            ClipboardContent::Text(text) => vec![
+                (atoms.UTF8_STRING, text.clone().into_bytes()),
+                (atoms.TEXT_PLAIN_UTF8, text.clone().into_bytes()),
+                (atoms.TEXT_PLAIN, text.into_bytes()),
+            ],
+            ClipboardContent::Html(html) => vec![(atoms.HTML, html.into_bytes())],
+            ClipboardContent::Rtf(rtf) => vec![(atoms.RTF, rtf.into_bytes())],
+            ClipboardContent::Image(image) => {
+                let png = image
+                    .to_png()
+                    .map_err(|err| Error::ImageEncode(err.to_string()))?;
+                vec![(atoms.PNG, png.get_bytes().to_vec())]
+            }
+            ClipboardContent::Files(files) => {
+                let paths: Vec<&str> = files
+                    .iter()
+                    .map(|file| file.strip_prefix("file://").unwrap_or(file))
+                    .collect();
+                vec![
+                    (atoms.URI_LIST, files.join("\r\n").into_bytes()),
+                    (
+                        atoms.GNOME_FILES,
+                        format!("copy\n{}", files.join("\n")).into_bytes(),
+                    ),
+                    (atoms.UTF8_STRING, paths.join("\n").into_bytes()),
+                ]
+            }
+            ClipboardContent::Other(format, data) => vec![(self.owner.atom(&format)?, data)],
+        })
+    }
+
+    /// Offer `data` and become the selection owner.
+    fn own(&self, data: Vec<(Atom, Vec<u8>)>) -> Result<()> {
+        *self.owned.lock()? = data;
+        self.owner.own(self.selection)
+    }
+}
+
+impl Drop for X11SelectionBackend {
+    fn drop(&mut self) {
+        // ends the thread serving the selection
+        let _ = self.owner.conn.destroy_window(self.owner.window);
+        let _ = self.owner.conn.flush();
+    }
+}
+
+/// Answer requests from other apps for the data we own, until the owner window is destroyed.
+fn serve(connection: &X11Connection, selection: Atom, owned: &Mutex<Vec<(Atom, Vec<u8>)>>) {
+    let mut transfers = Vec::new();
+    while let Ok(event) = connection.conn.wait_for_event() {
+        match event {
+            Event::SelectionRequest(request) if request.selection == selection => {
+                let _ = answer(connection, &request, owned, &mut transfers);
+            }
+            Event::PropertyNotify(notify) => {
+                let _ = continue_transfer(connection, &notify, &mut transfers);
+            }
+            Event::SelectionClear(clear) if clear.selection == selection => {
+                if let Ok(mut owned) = owned.lock() {
+                    owned.clear();
+                }
+            }
+            Event::DestroyNotify(destroy) if destroy.window == connection.window => break,
+            _ => {}
+        }
+    }
+}
+
+/// A large answer sent in chunks with the INCR protocol, the requestor deletes the property to ask for the next one.
+struct IncrTransfer {
+    requestor: Window,
+    property: Atom,
+    target: Atom,
+    data: Vec<u8>,
+    /// bytes already sent
+    sent: usize,
+    /// when the requestor last asked for a chunk
+    last: Instant,
+}
+
+fn answer(
+    connection: &X11Connection,
+    request: &SelectionRequestEvent,
+    owned: &Mutex<Vec<(Atom, Vec<u8>)>>,
+    transfers: &mut Vec<IncrTransfer>,
+) -> Result<()> {
+    let (conn, atoms) = (&connection.conn, &connection.atoms);
+    // obsolete clients pass no property, the target is used instead
+    let property = if request.property == NONE {
+        request.target
+    } else {
+        request.property
+    };
+    let owned = owned.lock()?;
+    let stored = if request.target == atoms.TARGETS {
+        let mut targets = vec![atoms.TARGETS];
+        targets.extend(owned.iter().map(|(target, _)| *target));
+        conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            AtomEnum::ATOM,
+            &targets,
+        )
+        .map_err(x11_error)?;
+        true
+    } else {
+        match owned.iter().find(|(target, _)| *target == request.target) {
+            Some((target, data)) if data.len() + 1024 < conn.maximum_request_bytes() => {
+                conn.change_property8(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    *target,
+                    data,
+                )
+                .map_err(x11_error)?;
+                true
+            }
+            // too large for one request: announce the size, the chunks follow as the requestor deletes the property
+            Some((target, data)) => {
+                conn.change_window_attributes(
+                    request.requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )
+                .map_err(x11_error)?;
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    atoms.INCR,
+                    &[data.len() as u32],
+                )
+                .map_err(x11_error)?;
+                transfers.push(IncrTransfer {
+                    requestor: request.requestor,
+                    property,
+                    target: *target,
+                    data: data.clone(),
+                    sent: 0,
+                    last: Instant::now(),
+                });
+                true
+            }
+            None => false,
+        }
+    };
+    drop(owned);
+    conn.send_event(
+        false,
+        request.requestor,
+        EventMask::NO_EVENT,
+        SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { NONE },
+        },
+    )
+    .map_err(x11_error)?;
+    conn.flush().map_err(x11_error)?;
+    Ok(())
+}
+
+/// Send the next chunk of a large answer once the requestor deleted the previous one, an empty chunk ends it.
+/// Transfers the requestor stopped asking for are dropped after `INCR_TIMEOUT`.
+fn continue_transfer(
+    connection: &X11Connection,
+    notify: &PropertyNotifyEvent,
+    transfers: &mut Vec<IncrTransfer>,
+) -> Result<()> {
+    transfers.retain(|transfer| transfer.last.elapsed() < INCR_TIMEOUT);
+    if notify.state != Property::DELETE {
+        return Ok(());
+    }
+    let Some(index) = transfers.iter().position(|transfer| {
+        transfer.requestor == notify.window && transfer.property == notify.atom
+    }) else {
+        return Ok(());
+    };
+    let conn = &connection.conn;
+    let transfer = &mut transfers[index];
+    let chunk = INCR_CHUNK.min(conn.maximum_request_bytes().saturating_sub(1024));
+    let end = (transfer.sent + chunk).min(transfer.data.len());
+    conn.change_property8(
+        PropMode::REPLACE,
+        transfer.requestor,
+        transfer.property,
+        transfer.target,
+        &transfer.data[transfer.sent..end],
+    )
+    .map_err(x11_error)?;
+    conn.flush().map_err(x11_error)?;
+    if transfer.sent == transfer.data.len() {
+        transfers.remove(index);
+    } else {
+        transfer.sent = end;
+        transfer.last = Instant::now();
+    }
+    Ok(())
+}
+
+impl ClipboardBackend for X11SelectionBackend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn has(&self, format: ContentFormat) -> Result<bool> {
+        let targets = self.reader.targets(self.selection)?;
+        Ok(self
+            .format_targets(&format)?
+            .iter()
+            .any(|target| targets.contains(target)))
+    }
+
+    fn available_formats(&self) -> Result<Vec<String>> {
+        let atoms = &self.reader.atoms;
+        let bookkeeping = [
+            atoms.TARGETS,
+            atoms.TIMESTAMP,
+            atoms.MULTIPLE,
+            atoms.SAVE_TARGETS,
+        ];
+        self.reader
+            .targets(self.selection)?
+            .into_iter()
+            .filter(|target| !bookkeeping.contains(target))
+            .map(|target| self.reader.atom_name(target))
+            .collect()
+    }
+
+    fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+        self.reader
+            .read(self.selection, self.reader.atom(format)?, READ_TIMEOUT)
+    }
+
+    /// Only text formats are measured, like on the regular clipboard.
+    fn buffer_size(&self, format: &str) -> Option<u64> {
+        if !normalized_mime(format)?.starts_with("text/") {
+            return None;
+        }
+        self.get_buffer(format)
+            .ok()
+            .map(|buffer| buffer.len() as u64)
+    }
+
+    fn get_text(&self) -> Result<String> {
+        self.read_string(ContentFormat::Text, "text")
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.read_string(ContentFormat::Html, "html")
+    }
+
+    fn get_rich_text(&self) -> Result<String> {
+        self.read_string(ContentFormat::Rtf, "rtf")
+    }
+
+    fn get_image(&self) -> Result<RustImageData> {
+        let (_, png) = self.read_format(ContentFormat::Image, "image")?;
+        RustImageData::from_bytes(&png).map_err(|err| Error::ImageDecode(err.to_string()))
+    }
+
+    fn get_files(&self) -> Result<Vec<String>> {
+        let uris = self.read_string(ContentFormat::Files, "files")?;
+        Ok(uris
+            .lines()
+            .map(str::trim)
+            .filter(|uri| uri.starts_with("file://"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn set_text(&self, text: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Text(text)])
+    }
+
+    fn set_html(&self, html: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Html(html)])
+    }
+
+    fn set_rich_text(&self, rtf: String) -> Result<()> {
+        self.set(vec![ClipboardContent::Rtf(rtf)])
+    }
+
+    fn set_image(&self, image: RustImageData) -> Result<()> {
+        self.set(vec![ClipboardContent::Image(image)])
+    }
+
+    fn set_files(&self, files: Vec<String>) -> Result<()> {
+        self.set(vec![ClipboardContent::Files(files)])
+    }
+
+    fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+        let mut data = vec![];
+        for content in contents {
+            for (target, bytes) in self.encode(content)? {
+                // the first content offering a target wins, e.g. text over the paths of files
+                if !data.iter().any(|(existing, _)| *existing == target) {
+                    data.push((target, bytes));
+                }
+            }
+        }
+        self.own(data)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.owned.lock()?.clear();
+        self.owner
+            .conn
+            .set_selection_owner(NONE, self.selection, CURRENT_TIME)
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)
+    }
+
+    fn watch(&self, mut on_change: Box<dyn FnMut() + Send>) -> Result<WatchHandle> {
+        let connection = X11Connection::open()?;
+        connection
+            .conn
+            .xfixes_query_version(5, 0)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(|err| Error::Clipboard(format!("xfixes is not available: {err}")))?;
+        connection
+            .conn
+            .xfixes_select_selection_input(
+                connection.root,
+                self.selection,
+                SelectionEventMask::SET_SELECTION_OWNER
+                    | SelectionEventMask::SELECTION_CLIENT_CLOSE
+                    | SelectionEventMask::SELECTION_WINDOW_DESTROY,
+            )
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+        let (stop, stopped) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            // poll, a blocked wait could not be interrupted by the handle
+            while stopped.recv_timeout(Duration::from_millis(100)) == Err(RecvTimeoutError::Timeout)
+            {
+                while let Ok(Some(event)) = connection.conn.poll_for_event() {
+                    if let Event::XfixesSelectionNotify(_) = event {
+                        on_change();
+                    }
+                }
+            }
+        });
+        Ok(WatchHandle::new(move || {
+            let _ = stop.send(());
+        }))
+    }
+}
+
+/// Keeps what the app wrote to CLIPBOARD through clipboard-rs available after the app exits.
+///
+/// clipboard-rs serves the data from a window it keeps to itself, so its owner is looked up after every write
+/// to tell at exit whether the clipboard still holds our data or another app took it over since.
+pub(crate) struct ClipboardKeeper {
+    connection: X11Connection,
+    /// the window that owned CLIPBOARD after our last write
+    owner: Cell<Window>,
+}
+
+impl ClipboardKeeper {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            connection: X11Connection::open()?,
+            owner: Cell::new(NONE),
+        })
+    }
+
+    /// Remember the clipboard owner after a write through clipboard-rs.
+    pub fn wrote(&self) {
+        let clipboard = self.connection.atoms.CLIPBOARD;
+        self.owner
+            .set(self.connection.selection_owner(clipboard).unwrap_or(NONE));
+    }
+
+    /// Hand the clipboard contents over as `policy` says, unless they are no longer ours.
+    pub fn persist(&self, policy: PersistOnExit) -> Result<()> {
+        let connection = &self.connection;
+        let clipboard = connection.atoms.CLIPBOARD;
+        let owner = self.owner.get();
+        if policy == PersistOnExit::Never
+            || owner == NONE
+            || connection.selection_owner(clipboard)? != owner
+        {
+            return Ok(());
+        }
+        // the manager copies every target from clipboard-rs, then answers
+        let saved = connection
+            .read(
+                connection.atoms.CLIPBOARD_MANAGER,
+                connection.atoms.SAVE_TARGETS,
+                SAVE_TIMEOUT,
+            )
+            .is_ok();
+        if saved {
+            return Ok(());
+        }
+        let PersistOnExit::ClipboardManagerOrServe { timeout } = policy else {
+            return Ok(());
+        };
+        if !SERVE_ENTRY_POINT.load(Ordering::SeqCst) {
+            return Err(Error::Clipboard(
+                "serving the clipboard after exit needs a call to serve_clipboard_if_requested at the start of main"
+                    .to_string(),
+            ));
+        }
+        let bookkeeping = [
+            connection.atoms.TARGETS,
+            connection.atoms.TIMESTAMP,
+            connection.atoms.MULTIPLE,
+            connection.atoms.SAVE_TARGETS,
+        ];
+        let data: Vec<(Atom, Vec<u8>)> = connection
+            .targets(clipboard)?
+            .into_iter()
+            .filter(|target| !bookkeeping.contains(target))
+            .filter_map(|target| {
+                Some((
+                    target,
+                    connection.read(clipboard, target, READ_TIMEOUT).ok()?,
+                ))
+            })
+            .collect();
+        if data.is_empty() {
+            return Ok(());
+        }
+        serve_detached(owner, data, timeout)
+    }
+}
+
+/// Serve `data` on CLIPBOARD from a process that outlives the app, until another app takes over the clipboard or
+/// `timeout` passes. The process is the app's own executable started again with [`SERVE_ARG`], it serves the
+/// data from [`serve_if_requested`] instead of starting the app. `owner` is the window that owns the clipboard now.
+fn serve_detached(owner: Window, data: Vec<(Atom, Vec<u8>)>, timeout: Duration) -> Result<()> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg(SERVE_ARG)
+        .arg(timeout.as_millis().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe. It detaches the server from the app's session, so closing the terminal
+    // does not end it
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+    let mut payload = owner.to_le_bytes().to_vec();
+    for (target, bytes) in &data {
+        payload.extend_from_slice(&target.to_le_bytes());
+        payload.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        payload.extend_from_slice(bytes);
+    }
+    // atoms are shared by every client of the X server, the server can use ours as they are
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(&payload)?;
+    drop(stdin);
+    // wait until it owns the clipboard, so there is no gap after the app is gone
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (ready, owned) = mpsc::channel();
+    thread::spawn(move || {
+        let mut byte = [0];
+        let _ = ready.send(stdout.read_exact(&mut byte).is_ok());
+    });
+    match owned.recv_timeout(SAVE_TIMEOUT) {
+        Ok(true) => Ok(()),
+        _ => {
+            let _ = child.kill();
+            Err(Error::Clipboard(
+                "the process serving the clipboard after exit did not start".to_string(),
+            ))
+        }
+    }
+}
+
+/// Serve the clipboard and exit when this process was started by [`serve_detached`], return otherwise.
+/// Remembers that the app calls it, see [`SERVE_ENTRY_POINT`].
+pub(crate) fn serve_if_requested() {
+    SERVE_ENTRY_POINT.store(true, Ordering::SeqCst);
+    let mut args = std::env::args_os().skip(1);
+    if args.next().as_deref() != Some(SERVE_ARG.as_ref()) {
+        return;
+    }
+    let timeout = args
+        .next()
+        .and_then(|timeout| timeout.to_str()?.parse().ok());
+    let code = match timeout.map(|timeout| serve_from_stdin(Duration::from_millis(timeout))) {
+        Some(Ok(())) => 0,
+        _ => 1,
+    };
+    std::process::exit(code);
+}
+
+fn truncated() -> Error {
+    Error::Clipboard("the clipboard payload is truncated".to_string())
+}
+
+/// Split a fixed size field off the front of `rest`.
+fn take<const N: usize>(rest: &mut &[u8]) -> Result<[u8; N]> {
+    if rest.len() < N {
+        return Err(truncated());
+    }
+    let (head, tail) = rest.split_at(N);
+    *rest = tail;
+    Ok(head.try_into().expect("split at N"))
+}
+
+fn serve_from_stdin(timeout: Duration) -> Result<()> {
+    let mut payload = Vec::new();
+    std::io::stdin().read_to_end(&mut payload)?;
+    let mut rest = payload.as_slice();
+    let owner = Window::from_le_bytes(take(&mut rest)?);
+    let mut data = Vec::new();
+    while !rest.is_empty() {
+        let target = Atom::from_le_bytes(take(&mut rest)?);
+        let len = u64::from_le_bytes(take(&mut rest)?) as usize;
+        if rest.len() < len {
+            return Err(truncated());
+        }
+        let (bytes, tail) = rest.split_at(len);
+        data.push((target, bytes.to_vec()));
+        rest = tail;
+    }
+    let connection = X11Connection::open()?;
+    let clipboard = connection.atoms.CLIPBOARD;
+    // something else was copied while the app exited, it is not ours to take over
+    if connection.selection_owner(clipboard)? != owner {
+        return Ok(());
+    }
+    connection.own(clipboard)?;
+    let mut stdout = std::io::stdout();
+    stdout.write_all(&[1])?;
+    stdout.flush()?;
+    let deadline = Instant::now() + timeout;
+    let owned = Mutex::new(data);
+    let mut transfers = Vec::new();
+    let mut cleared = false;
+    // finish large transfers that are under way when another app takes over
+    while !cleared || !transfers.is_empty() {
+        let Ok(Some(event)) = connection.next_event(deadline) else {
+            break;
+        };
+        match event {
+            Event::SelectionRequest(request) if request.selection == clipboard && !cleared => {
+                let _ = answer(&connection, &request, &owned, &mut transfers);
+            }
+            Event::PropertyNotify(notify) => {
+                let _ = continue_transfer(&connection, &notify, &mut transfers);
+            }
+            Event::SelectionClear(clear) if clear.selection == clipboard => cleared = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
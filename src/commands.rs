@@ -1,100 +1,292 @@
-use crate::Clipboard;
-use tauri::{command, AppHandle, Runtime, State, Window};
+use crate::policy::{check_read, Invoker};
+use crate::scope::{ClipboardScope, ScopeAccess};
+use crate::{Clipboard, ClipboardSelection, Error, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{de::IntoDeserializer, Deserialize};
+use tauri::{
+    command,
+    ipc::{Channel, InvokeBody, Request, Response},
+    AppHandle, Runtime, State, Webview, Window,
+};
 
+/// Bytes sent as the raw request body, or as the number array argument `name` of a json request.
+fn request_bytes(request: &Request<'_>, name: &str) -> Result<Vec<u8>> {
+    match request.body() {
+        InvokeBody::Raw(bytes) => Ok(bytes.clone()),
+        InvokeBody::Json(args) => {
+            let value = args
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::InvalidRequest(format!("missing argument {name}")))?;
+            Vec::<u8>::deserialize(value).map_err(|err| Error::InvalidRequest(err.to_string()))
+        }
+    }
+}
+
+/// String argument `name`, from the headers of a raw request or the json arguments.
+fn request_arg(request: &Request<'_>, name: &str) -> Result<String> {
+    optional_request_arg(request, name)?
+        .ok_or_else(|| Error::InvalidRequest(format!("missing argument {name}")))
+}
+
+fn optional_request_arg(request: &Request<'_>, name: &str) -> Result<Option<String>> {
+    Ok(match request.body() {
+        InvokeBody::Raw(_) => request
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().map(str::to_string))
+            .transpose()
+            .map_err(|err| Error::InvalidRequest(err.to_string()))?,
+        InvokeBody::Json(args) => args
+            .get(name)
+            .and_then(|value| value.as_str())
+            .map(str::to_string),
+    })
+}
+
+/// The clipboard of the optional `selection` argument, the regular clipboard when it is missing.
+fn selected(clipboard: &Clipboard, selection: Option<ClipboardSelection>) -> Result<&Clipboard> {
+    clipboard.selection(selection.unwrap_or_default())
+}
+
+/// [`selected`] for commands taking a raw request, the selection is passed like [`request_arg`].
+fn request_selection<'a>(clipboard: &'a Clipboard, request: &Request<'_>) -> Result<&'a Clipboard> {
+    let selection = optional_request_arg(request, "selection")?
+        .map(|name| {
+            ClipboardSelection::deserialize(name.into_deserializer())
+                .map_err(|err: serde::de::value::Error| Error::InvalidRequest(err.to_string()))
+        })
+        .transpose()?;
+    selected(clipboard, selection)
+}
+
+/// whether the clipboard holds text, `false` when the scope does not let the caller read it
 #[command]
 pub fn has_text<R: Runtime>(
     _app: AppHandle<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<bool, String> {
-    clipboard.has_text()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<bool> {
+    check_read(&invoker)?;
+    if scope.check_format("text", ScopeAccess::Read).is_err() {
+        return Ok(false);
+    }
+    selected(&clipboard, selection)?.has_text()
 }
 
+/// whether the clipboard holds image, `false` when the scope does not let the caller read it
 #[command]
 pub fn has_image<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<bool, String> {
-    clipboard.has_image()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<bool> {
+    check_read(&invoker)?;
+    if scope.check_format("image", ScopeAccess::Read).is_err() {
+        return Ok(false);
+    }
+    selected(&clipboard, selection)?.has_image()
 }
 
+/// whether the clipboard holds html, `false` when the scope does not let the caller read it
 #[command]
 pub fn has_html<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<bool, String> {
-    clipboard.has_html()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<bool> {
+    check_read(&invoker)?;
+    if scope.check_format("html", ScopeAccess::Read).is_err() {
+        return Ok(false);
+    }
+    selected(&clipboard, selection)?.has_html()
 }
 
+/// whether the clipboard holds rtf, `false` when the scope does not let the caller read it
 #[command]
 pub fn has_rtf<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<bool, String> {
-    clipboard.has_rtf()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<bool> {
+    check_read(&invoker)?;
+    if scope.check_format("rtf", ScopeAccess::Read).is_err() {
+        return Ok(false);
+    }
+    selected(&clipboard, selection)?.has_rtf()
 }
 
+/// whether the clipboard holds files, `false` when the scope does not let the caller read it
 #[command]
 pub fn has_files<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<bool> {
+    check_read(&invoker)?;
+    if scope.check_format("files", ScopeAccess::Read).is_err() {
+        return Ok(false);
+    }
+    selected(&clipboard, selection)?.has_files()
+}
+
+/// the formats on the clipboard that the scope lets the caller read
+#[command]
+pub fn available_types<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<crate::desktop::AvailableTypes> {
+    check_read(&invoker)?;
+    let types = selected(&clipboard, selection)?.available_types()?;
+    Ok(scope.restrict_types(types))
+}
+
+/// every raw format on the clipboard that the scope lets the caller read, with its mime type and size where known
+#[command]
+pub async fn available_formats<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<Vec<crate::FormatInfo>> {
+    check_read(&invoker)?;
+    let formats = selected(&clipboard, selection)?.available_formats()?;
+    Ok(formats
+        .into_iter()
+        .filter(|format| {
+            scope
+                .check_buffer_format(&format.name, ScopeAccess::Read)
+                .is_ok()
+        })
+        .collect())
+}
+
+/// raw bytes of a format, returned as an `ArrayBuffer` instead of a json array
+#[command]
+pub async fn read_buffer<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<bool, String> {
-    clipboard.has_files()
+    scope: ClipboardScope,
+    format: String,
+    selection: Option<ClipboardSelection>,
+) -> Result<Response> {
+    check_read(&invoker)?;
+    scope.check_buffer_format(&format, ScopeAccess::Read)?;
+    let buffer = selected(&clipboard, selection)?.read_buffer(&format)?;
+    scope.check_buffer(&format, &buffer, ScopeAccess::Read)?;
+    Ok(Response::new(buffer))
 }
 
+/// read every available format at once, `options` selects which contents to include
 #[command]
-pub fn available_types(
+pub async fn read_all<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<crate::desktop::AvailableTypes, String> {
-    clipboard.available_types()
+    scope: ClipboardScope,
+    options: Option<crate::SnapshotOptions>,
+    selection: Option<ClipboardSelection>,
+) -> Result<crate::ClipboardSnapshot> {
+    check_read(&invoker)?;
+    let snapshot = selected(&clipboard, selection)?.snapshot(&options.unwrap_or_default())?;
+    Ok(scope.restrict_snapshot(snapshot))
+}
+
+/// save everything on the clipboard inside the plugin and return a handle to restore it with
+#[command]
+pub async fn save_snapshot<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    selection: Option<ClipboardSelection>,
+) -> Result<u64> {
+    check_read(&invoker)?;
+    selected(&clipboard, selection)?.save_snapshot_handle(invoker.webview.label())
 }
 
 #[command]
 pub fn read_text<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<String, String> {
-    clipboard.read_text()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<String> {
+    check_read(&invoker)?;
+    scope.check_format("text", ScopeAccess::Read)?;
+    let text = selected(&clipboard, selection)?.read_text()?;
+    scope.check("text", text.len(), ScopeAccess::Read)?;
+    Ok(text)
 }
 
 #[command]
 pub fn read_html<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<String, String> {
-    clipboard.read_html()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<String> {
+    check_read(&invoker)?;
+    scope.check_format("html", ScopeAccess::Read)?;
+    let html = selected(&clipboard, selection)?.read_html()?;
+    scope.check("html", html.len(), ScopeAccess::Read)?;
+    Ok(html)
 }
 
 #[command]
 pub fn read_rtf<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<String, String> {
-    clipboard.read_rtf()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<String> {
+    check_read(&invoker)?;
+    scope.check_format("rtf", ScopeAccess::Read)?;
+    let rtf = selected(&clipboard, selection)?.read_rtf()?;
+    scope.check("rtf", rtf.len(), ScopeAccess::Read)?;
+    Ok(rtf)
 }
 
 #[command]
 pub fn read_files<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<Vec<String>, String> {
-    clipboard.read_files()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<Vec<String>> {
+    check_read(&invoker)?;
+    scope.check_format("files", ScopeAccess::Read)?;
+    scope.read_files(selected(&clipboard, selection)?.read_files()?)
 }
 
 #[command]
 pub fn read_files_uris<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<Vec<String>, String> {
-    clipboard.read_files_uris()
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<Vec<String>> {
+    check_read(&invoker)?;
+    scope.check_format("files", ScopeAccess::Read)?;
+    scope.read_files(selected(&clipboard, selection)?.read_files_uris()?)
 }
 
 #[command]
@@ -102,9 +294,12 @@ pub fn write_files_uris<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     files_uris: Vec<String>,
-) -> Result<(), String> {
-    clipboard.write_files_uris(files_uris)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check_write_files(&files_uris)?;
+    selected(&clipboard, selection)?.write_files_uris(files_uris)
 }
 
 #[command]
@@ -112,14 +307,16 @@ pub fn write_files<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     files_paths: Vec<String>,
-) -> Result<(), String> {
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
     for file in &files_paths {
         if file.starts_with("file://") {
-            return Err(format!(
-                "Invalid file uri: {}. File uri should not start with file://",
-                file
-            ));
+            return Err(Error::InvalidFileUri {
+                uri: file.clone(),
+                reason: "file path should not start with file://",
+            });
         }
     }
     let mut files_uris: Vec<String> = vec![];
@@ -136,7 +333,7 @@ pub fn write_files<R: Runtime>(
             files_uris.push(file.clone())
         }
     }
-    write_files_uris(_app, _window, clipboard, files_uris)
+    write_files_uris(_app, _window, clipboard, scope, files_uris, selection)
 }
 
 #[command]
@@ -144,9 +341,27 @@ pub fn write_text<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    text: String,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check("text", text.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?.write_text(text)
+}
+
+#[command]
+pub fn write_sensitive_text<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     text: String,
-) -> Result<(), String> {
-    clipboard.write_text(text)
+    clear_after_ms: Option<u64>,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check("text", text.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?
+        .write_sensitive_text(text, clear_after_ms.map(std::time::Duration::from_millis))
 }
 
 #[command]
@@ -154,9 +369,12 @@ pub fn write_html<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     html: String,
-) -> Result<(), String> {
-    clipboard.write_html(html)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check("html", html.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?.write_html(html)
 }
 
 #[command]
@@ -164,10 +382,14 @@ pub fn write_html_and_text<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     html: String,
     text: String,
-) -> Result<(), String> {
-    clipboard.write_html_and_text(html, text)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check("html", html.len(), ScopeAccess::Write)?;
+    scope.check("text", text.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?.write_html_and_text(html, text)
 }
 
 #[command]
@@ -175,28 +397,103 @@ pub fn write_rtf<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     rtf: String,
-) -> Result<(), String> {
-    clipboard.write_rtf(rtf)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    scope.check("rtf", rtf.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?.write_rtf(rtf)
 }
 
-/// read image from clipboard and return a base64 string
+/// read image from clipboard and return a base64 string, png unless another `encoding` is given
 #[command]
 pub async fn read_image_base64<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<String, String> {
-    clipboard.read_image_base64()
+    scope: ClipboardScope,
+    encoding: Option<crate::ImageEncoding>,
+    selection: Option<ClipboardSelection>,
+) -> Result<String> {
+    check_read(&invoker)?;
+    scope.check_format("image", ScopeAccess::Read)?;
+    let image = selected(&clipboard, selection)?.read_image(encoding.unwrap_or_default())?;
+    scope.check("image", image.data.len(), ScopeAccess::Read)?;
+    Ok(general_purpose::STANDARD.encode(image.data))
 }
 
+/// image file bytes, returned as an `ArrayBuffer` instead of a json array
 #[command]
 pub async fn read_image_binary<R: Runtime>(
     _app: AppHandle<R>,
-    _window: Window<R>,
+    invoker: Invoker<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<Vec<u8>, String> {
-    clipboard.read_image_binary()
+    scope: ClipboardScope,
+    encoding: Option<crate::ImageEncoding>,
+    selection: Option<ClipboardSelection>,
+) -> Result<Response> {
+    check_read(&invoker)?;
+    scope.check_format("image", ScopeAccess::Read)?;
+    let image = selected(&clipboard, selection)?.read_image(encoding.unwrap_or_default())?;
+    scope.check("image", image.data.len(), ScopeAccess::Read)?;
+    Ok(Response::new(image.data))
+}
+
+/// read image from clipboard in the given encoding, together with its size, as a raw `ArrayBuffer`
+#[command]
+pub async fn read_image<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    encoding: Option<crate::ImageEncoding>,
+    selection: Option<ClipboardSelection>,
+) -> Result<Response> {
+    check_read(&invoker)?;
+    scope.check_format("image", ScopeAccess::Read)?;
+    let image = selected(&clipboard, selection)?.read_image(encoding.unwrap_or_default())?;
+    scope.check("image", image.data.len(), ScopeAccess::Read)?;
+    Ok(Response::new(image.into_response_body()))
+}
+
+/// size and pixel layout of the clipboard image, without transferring it
+#[command]
+pub async fn read_image_info<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    selection: Option<ClipboardSelection>,
+) -> Result<crate::ImageInfo> {
+    check_read(&invoker)?;
+    scope.check_format("image", ScopeAccess::Read)?;
+    selected(&clipboard, selection)?.read_image_info()
+}
+
+/// preview of the clipboard image that fits in `max_width` x `max_height`, png unless another `encoding` is given,
+/// laid out like the `read_image` response
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn read_image_thumbnail<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    max_width: u32,
+    max_height: u32,
+    filter: Option<crate::ThumbnailFilter>,
+    encoding: Option<crate::ImageEncoding>,
+    selection: Option<ClipboardSelection>,
+) -> Result<Response> {
+    check_read(&invoker)?;
+    scope.check_format("image", ScopeAccess::Read)?;
+    let thumbnail = selected(&clipboard, selection)?.read_image_thumbnail(
+        max_width,
+        max_height,
+        filter.unwrap_or_default(),
+        encoding.unwrap_or_default(),
+    )?;
+    scope.check("image", thumbnail.data.len(), ScopeAccess::Read)?;
+    Ok(Response::new(thumbnail.into_response_body()))
 }
 
 /// write base64 image to clipboard
@@ -205,19 +502,86 @@ pub async fn write_image_base64<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
     base64_image: String,
-) -> Result<(), String> {
-    clipboard.write_image_base64(base64_image)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    // the size limit applies to the image file, like for write_image_binary
+    let bytes = crate::encoding::decode_base64_image(&base64_image)?;
+    scope.check("image", bytes.len(), ScopeAccess::Write)?;
+    selected(&clipboard, selection)?.write_image_binary(bytes)
 }
 
+/// write image file bytes, sent as the raw request body or as the `bytes` json argument.
+/// An optional `selection` is passed as a header or json argument
 #[command]
 pub async fn write_image_binary<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
-    bytes: Vec<u8>,
-) -> Result<(), String> {
-    clipboard.write_image_binary(bytes)
+    scope: ClipboardScope,
+    request: Request<'_>,
+) -> Result<()> {
+    let bytes = request_bytes(&request, "bytes")?;
+    scope.check("image", bytes.len(), ScopeAccess::Write)?;
+    request_selection(&clipboard, &request)?.write_image_binary(bytes)
+}
+
+/// write raw bytes under a format name. The bytes are sent as the raw request body with the name in the `format` header,
+/// or as the `format` and `buffer` json arguments. An optional `selection` is passed the same way as the name
+#[command]
+pub async fn write_buffer<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    request: Request<'_>,
+) -> Result<()> {
+    let (format, buffer) = (
+        request_arg(&request, "format")?,
+        request_bytes(&request, "buffer")?,
+    );
+    scope.check_buffer(&format, &buffer, ScopeAccess::Write)?;
+    request_selection(&clipboard, &request)?.write_buffer(format, buffer)
+}
+
+/// write several representations at once, replacing everything on the clipboard
+#[command]
+pub async fn write_contents<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    contents: Vec<crate::ClipboardItemContent>,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    for content in &contents {
+        scope.check_content(content)?;
+    }
+    selected(&clipboard, selection)?.write_contents(contents)
+}
+
+#[command]
+pub async fn restore_snapshot<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    id: u64,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    selected(&clipboard, selection)?.restore_snapshot_handle_scoped(&scope, webview.label(), id)
+}
+
+#[command]
+pub fn discard_snapshot<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    clipboard: State<'_, Clipboard>,
+    id: u64,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    selected(&clipboard, selection)?.discard_snapshot_handle(webview.label(), id)
 }
 
 #[command]
@@ -225,30 +589,171 @@ pub fn clear<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     clipboard: State<'_, Clipboard>,
-) -> Result<(), String> {
-    clipboard.clear()
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    selected(&clipboard, selection)?.clear()
 }
 
+/// start the monitor for the calling webview or join the running one, change events are sent over `on_event`
+/// to it alone
 #[command]
 pub async fn start_monitor<R: Runtime>(
     app: tauri::AppHandle<R>,
+    invoker: Invoker<R>,
     state: tauri::State<'_, Clipboard>,
-) -> Result<(), String> {
-    state.start_monitor(app)
+    scope: ClipboardScope,
+    on_event: Channel<crate::ClipboardChangeEvent>,
+    options: Option<crate::MonitorOptions>,
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    check_read(&invoker)?;
+    selected(&state, selection)?.start_monitor_for(
+        app,
+        invoker.webview,
+        options.unwrap_or_default(),
+        scope,
+        on_event,
+    )
 }
 
 #[command]
 pub async fn stop_monitor<R: Runtime>(
     app: tauri::AppHandle<R>,
+    webview: Webview<R>,
     state: tauri::State<'_, Clipboard>,
-) -> Result<(), String> {
-    state.stop_monitor(app)
+    selection: Option<ClipboardSelection>,
+) -> Result<()> {
+    selected(&state, selection)?.stop_monitor_for(app, webview.label())
 }
 
 #[command]
 pub fn is_monitor_running<R: Runtime>(
     _app: tauri::AppHandle<R>,
     state: tauri::State<'_, Clipboard>,
+    selection: Option<ClipboardSelection>,
 ) -> bool {
-    state.is_monitor_running()
+    selected(&state, selection)
+        .map(|clipboard| clipboard.is_monitor_running())
+        .unwrap_or(false)
+}
+
+#[command]
+pub fn is_available<R: Runtime>(
+    _app: tauri::AppHandle<R>,
+    state: tauri::State<'_, Clipboard>,
+) -> bool {
+    state.is_available()
+}
+
+/// selections this platform has, e.g. `["clipboard", "primary", "secondary"]` on Linux
+#[command]
+pub fn available_selections<R: Runtime>(
+    _app: tauri::AppHandle<R>,
+    state: tauri::State<'_, Clipboard>,
+) -> Vec<ClipboardSelection> {
+    state.available_selections()
+}
+
+#[command]
+pub fn backend_status<R: Runtime>(
+    _app: tauri::AppHandle<R>,
+    state: tauri::State<'_, Clipboard>,
+) -> Result<crate::desktop::BackendStatus> {
+    state.backend_status()
+}
+
+/// note that the user just interacted with the calling webview, for [`crate::ReadPolicy::gesture_timeout`]
+#[command]
+pub fn report_user_gesture<R: Runtime>(
+    webview: Webview<R>,
+    policy: State<'_, crate::policy::AccessPolicy>,
+) -> Result<()> {
+    policy.record_gesture(webview.label())
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_list<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+) -> Result<Vec<crate::HistoryEntry>> {
+    check_read(&invoker)?;
+    clipboard.history_list_scoped(&scope)
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_page<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    offset: usize,
+    limit: usize,
+) -> Result<crate::HistoryPage> {
+    check_read(&invoker)?;
+    clipboard.history_page_scoped(&scope, offset, limit)
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_search<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    query: crate::HistoryQuery,
+) -> Result<Vec<crate::HistoryMatch>> {
+    check_read(&invoker)?;
+    clipboard.history_search_scoped(&scope, &query)
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_get<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    id: u64,
+) -> Result<crate::HistoryEntry> {
+    check_read(&invoker)?;
+    clipboard.history_get_scoped(&scope, id)
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_delete<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    id: u64,
+) -> Result<()> {
+    check_read(&invoker)?;
+    clipboard.history_delete_scoped(&scope, id)
+}
+
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub fn history_pin<R: Runtime>(
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    id: u64,
+    pinned: bool,
+) -> Result<()> {
+    check_read(&invoker)?;
+    clipboard.history_pin_scoped(&scope, id, pinned)
+}
+
+/// write every format of a history entry back to the clipboard
+#[cfg(all(desktop, feature = "history"))]
+#[command]
+pub async fn history_copy<R: Runtime>(
+    _app: AppHandle<R>,
+    invoker: Invoker<R>,
+    clipboard: State<'_, Clipboard>,
+    scope: ClipboardScope,
+    id: u64,
+) -> Result<()> {
+    check_read(&invoker)?;
+    clipboard.history_copy_scoped(&scope, id)
 }
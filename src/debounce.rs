@@ -0,0 +1,232 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    time::{Duration, Instant},
+};
+
+/// Which edge of a burst of clipboard notifications produces an event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebounceEdge {
+    /// emit on the first notification, ignore the rest of the burst
+    Leading,
+    /// emit once the burst is over, i.e. no notification arrived for the debounce interval
+    #[default]
+    Trailing,
+    /// emit on the first notification, and again after the burst if more notifications arrived
+    Both,
+}
+
+/// How the monitor turns bursts of OS notifications into change events.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DebounceConfig {
+    /// quiet period that ends a burst, zero disables debouncing
+    pub interval: Duration,
+    pub edge: DebounceEdge,
+    /// minimum time between two events, `None` for no cap
+    pub min_gap: Option<Duration>,
+}
+
+impl DebounceConfig {
+    fn is_passthrough(&self) -> bool {
+        self.interval.is_zero() && self.min_gap.is_none()
+    }
+}
+
+/// Wrap `fire` so it runs according to `config`. Without debouncing or rate cap `fire` runs on the watcher thread
+/// directly, otherwise on a worker thread that exits once the returned closure is dropped (i.e. the watcher stopped).
+pub(crate) fn debounced(
+    config: DebounceConfig,
+    mut fire: impl FnMut() + Send + 'static,
+) -> Box<dyn FnMut() + Send> {
+    if config.is_passthrough() {
+        return Box::new(fire);
+    }
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut worker = Worker {
+            config,
+            receiver,
+            last_fired: None,
+        };
+        worker.run(&mut fire);
+    });
+    Box::new(move || {
+        let _ = sender.send(());
+    })
+}
+
+struct Worker {
+    config: DebounceConfig,
+    receiver: Receiver<()>,
+    last_fired: Option<Instant>,
+}
+
+impl Worker {
+    fn run(&mut self, fire: &mut dyn FnMut()) {
+        let leading = matches!(self.config.edge, DebounceEdge::Leading | DebounceEdge::Both);
+        let trailing = matches!(
+            self.config.edge,
+            DebounceEdge::Trailing | DebounceEdge::Both
+        );
+        // each iteration handles one burst
+        while self.receiver.recv().is_ok() {
+            let mut pending = true;
+            if leading {
+                if !self.fire(fire) {
+                    return;
+                }
+                pending = false;
+            }
+            let mut deadline = Instant::now() + self.config.interval;
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(()) => {
+                        pending = true;
+                        deadline = Instant::now() + self.config.interval;
+                    }
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if trailing && pending && !self.fire(fire) {
+                return;
+            }
+        }
+    }
+
+    /// Run `fire` once the minimum gap since the last event passed, false when the watcher stopped meanwhile.
+    fn fire(&mut self, fire: &mut dyn FnMut()) -> bool {
+        if let (Some(min_gap), Some(last_fired)) = (self.config.min_gap, self.last_fired) {
+            let deadline = last_fired + min_gap;
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(()) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return false,
+                }
+            }
+        }
+        // the event reads the clipboard when it fires, so notifications queued until now are covered by it
+        loop {
+            match self.receiver.try_recv() {
+                Ok(()) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+        fire();
+        self.last_fired = Some(Instant::now());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    /// `debounced` reporting when `fire` ran.
+    fn recorder(config: DebounceConfig) -> (Box<dyn FnMut() + Send>, Receiver<Instant>) {
+        let (sender, fired) = mpsc::channel();
+        let notify = debounced(config, move || {
+            let _ = sender.send(Instant::now());
+        });
+        (notify, fired)
+    }
+
+    fn config(edge: DebounceEdge) -> DebounceConfig {
+        DebounceConfig {
+            interval: INTERVAL,
+            edge,
+            min_gap: None,
+        }
+    }
+
+    /// Three notifications 20ms apart, well within the interval.
+    fn burst(notify: &mut dyn FnMut()) -> Instant {
+        for _ in 0..3 {
+            notify();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Instant::now()
+    }
+
+    fn count(fired: &Receiver<Instant>) -> usize {
+        std::thread::sleep(INTERVAL * 3);
+        fired.try_iter().count()
+    }
+
+    #[test]
+    fn passthrough_fires_on_the_calling_thread() {
+        let (mut notify, fired) = recorder(DebounceConfig::default());
+        notify();
+        notify();
+        assert_eq!(fired.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn trailing_fires_once_after_the_burst() {
+        let (mut notify, fired) = recorder(config(DebounceEdge::Trailing));
+        let end = burst(&mut *notify);
+        let first = fired.recv_timeout(INTERVAL * 5).unwrap();
+        assert!(first >= end);
+        assert_eq!(count(&fired), 0);
+    }
+
+    #[test]
+    fn leading_fires_once_at_the_start() {
+        let (mut notify, fired) = recorder(config(DebounceEdge::Leading));
+        let end = burst(&mut *notify);
+        let first = fired.recv_timeout(INTERVAL * 5).unwrap();
+        assert!(first < end);
+        assert_eq!(count(&fired), 0);
+    }
+
+    #[test]
+    fn both_fires_at_the_start_and_after_the_burst() {
+        let (mut notify, fired) = recorder(config(DebounceEdge::Both));
+        burst(&mut *notify);
+        assert_eq!(count(&fired), 2);
+        // a single notification is not repeated at the trailing edge
+        notify();
+        assert_eq!(count(&fired), 1);
+    }
+
+    #[test]
+    fn min_gap_spaces_events() {
+        let gap = Duration::from_millis(150);
+        let (mut notify, fired) = recorder(DebounceConfig {
+            interval: Duration::ZERO,
+            edge: DebounceEdge::Trailing,
+            min_gap: Some(gap),
+        });
+        for _ in 0..3 {
+            notify();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let first = fired.recv_timeout(gap * 3).unwrap();
+        let second = fired.recv_timeout(gap * 3).unwrap();
+        assert!(second - first >= gap);
+        // the notifications queued during the gap are covered by one event
+        assert!(fired.recv_timeout(gap * 2).is_err());
+    }
+
+    #[test]
+    fn stopping_during_the_gap_drops_the_waiting_event() {
+        let gap = Duration::from_millis(150);
+        let (mut notify, fired) = recorder(DebounceConfig {
+            interval: Duration::ZERO,
+            edge: DebounceEdge::Trailing,
+            min_gap: Some(gap),
+        });
+        notify();
+        fired.recv_timeout(gap * 3).unwrap();
+        // waits for the gap, until the watcher stops
+        notify();
+        std::thread::sleep(gap / 3);
+        drop(notify);
+        assert!(fired.recv_timeout(gap * 2).is_err());
+    }
+}
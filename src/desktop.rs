@@ -1,22 +1,129 @@
+use crate::backend::{
+    normalized_mime, ClipboardBackend, ClipboardRsBackend, ClipboardSelection, PersistOnExit,
+    UnavailableBackend, WatchHandle, SENSITIVE_HINTS, STANDARD_FORMATS,
+};
+use crate::debounce::debounced;
+use crate::encoding::{
+    decode_base64_image, decode_image, encode_image, image_info, thumbnail, EncodedImage,
+    ImageEncoding, ImageInfo, ThumbnailFilter,
+};
+use crate::expiry::{clear_if_unchanged, Expiry, ExpiryConfig, CLIPBOARD_EXPIRED_EVENT};
+use crate::monitor::{
+    content_fingerprint, ChangeTracker, ClipboardChangeEvent, ClipboardMonitor, MonitorConfig,
+    MonitorListener, MonitorOptions, MonitorUsers, Subscribers, Subscription, SubscriptionId,
+    MONITOR_STATUS_EVENT,
+};
+use crate::scope::ClipboardScope;
+use crate::{Error, Result};
 use base64::{engine::general_purpose, Engine as _};
 use clipboard_rs::{
-    common::RustImage, Clipboard as ClipboardRS, ClipboardContent,
-    ClipboardContext as ClipboardRsContext, ClipboardHandler, ClipboardWatcher,
-    ClipboardWatcherContext, ContentFormat, RustImageData, WatcherShutdown,
+    common::RustImage, ClipboardContent, ClipboardHandler, ContentFormat, RustImageData,
 };
-use image::EncodableLayout;
+use image::DynamicImage;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use tauri::{plugin::PluginApi, AppHandle, Emitter, Runtime};
-
-pub fn init<R: Runtime, C: DeserializeOwned>(_api: PluginApi<R, C>) -> crate::Result<Clipboard> {
-    Ok(Clipboard {
-        clipboard: Arc::new(Mutex::new(ClipboardRsContext::new().unwrap())),
-        watcher_shutdown: Arc::default(),
-    })
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+    time::Duration,
+};
+use tauri::{ipc::Channel, plugin::PluginApi, AppHandle, Emitter, Runtime, Webview};
+
+/// The system clipboard: data-control on Wayland sessions whose compositor supports it, clipboard-rs otherwise.
+fn system_backend() -> Box<dyn ClipboardBackend> {
+    #[cfg(all(
+        unix,
+        not(any(
+            target_os = "macos",
+            target_os = "android",
+            target_os = "ios",
+            target_os = "emscripten"
+        ))
+    ))]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        // GNOME has no data-control, there clipboard-rs still works through XWayland
+        if let Ok(backend) = crate::backend::WaylandBackend::new() {
+            return Box::new(backend);
+        }
+    }
+    match ClipboardRsBackend::new() {
+        Ok(backend) => Box::new(backend),
+        // keep the app running, every clipboard call will report the reason instead
+        Err(err) => Box::new(UnavailableBackend::new("clipboard-rs", err.to_string())),
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub fn init<R: Runtime, C: DeserializeOwned>(
+    _api: PluginApi<R, C>,
+    backend: Option<Box<dyn ClipboardBackend>>,
+    monitor_config: MonitorConfig,
+    expiry_config: ExpiryConfig,
+) -> crate::Result<Clipboard> {
+    let backend = backend.unwrap_or_else(system_backend);
+    let selections: Vec<_> = [ClipboardSelection::Primary, ClipboardSelection::Secondary]
+        .into_iter()
+        .filter_map(|selection| Some((selection, backend.selection(selection)?)))
+        .collect();
+    let mut clipboard = Clipboard {
+        selection: ClipboardSelection::Clipboard,
+        clipboard: Arc::new(Mutex::new(backend)),
+        watcher: Arc::default(),
+        monitor_users: Arc::default(),
+        monitor_config,
+        expiry: Arc::new(Expiry::new(expiry_config)),
+        tracker: Arc::default(),
+        seq: Arc::default(),
+        subscribers: Subscribers::default(),
+        saved: Arc::default(),
+        selections: HashMap::new(),
+        #[cfg(feature = "history")]
+        history: None,
+    };
+    for (selection, backend) in selections {
+        let other = clipboard.for_selection(selection, backend);
+        clipboard.selections.insert(selection, other);
+    }
+    Ok(clipboard)
+}
+
+/// validate the uri format expected by the platform's clipboard
+fn check_file_uris(files: &[String]) -> Result<()> {
+    // iterate through files, check if it starts with files://, if not throw error (only linux and mac)
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        for file in files {
+            if !file.starts_with("file://") {
+                return Err(Error::InvalidFileUri {
+                    uri: file.clone(),
+                    reason: "file uri should start with file://",
+                });
+            }
+        }
+    }
+    // On Windows, we don't need the file:// prefix, so we remove it if it's there
+    #[cfg(target_os = "windows")]
+    {
+        for file in files {
+            if file.starts_with("file://") {
+                return Err(Error::InvalidFileUri {
+                    uri: file.clone(),
+                    reason: "file uri on Windows should not start with file://",
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// encode a clipboard image as png bytes
+pub(crate) fn encode_png(image: &RustImageData) -> Result<Vec<u8>> {
+    Ok(image
+        .to_png()
+        .map_err(|err| Error::ImageEncode(err.to_string()))?
+        .get_bytes()
+        .to_vec())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AvailableTypes {
     pub text: bool,
     pub html: bool,
@@ -25,21 +132,225 @@ pub struct AvailableTypes {
     pub files: bool,
 }
 
+/// A raw format offered by the clipboard, listed by [`Clipboard::available_formats`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatInfo {
+    /// platform identifier, pass it to [`Clipboard::read_buffer`]
+    pub name: String,
+    /// mime type the name corresponds to, e.g. `text/plain` for `UTF8_STRING`, `None` for bookkeeping targets like `TARGETS`
+    pub mime: Option<String>,
+    /// payload size in bytes, only where it is cheap to find out
+    pub size: Option<u64>,
+}
+
+/// Which formats [`Clipboard::snapshot`] reads, all of them by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnapshotOptions {
+    pub text: bool,
+    pub html: bool,
+    pub rtf: bool,
+    pub files: bool,
+    /// encoding the image as png is by far the most expensive part of a snapshot
+    pub image: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            text: true,
+            html: true,
+            rtf: true,
+            files: true,
+            image: true,
+        }
+    }
+}
+
+/// Every representation on the clipboard, read at the same moment.
+/// Formats skipped by [`SnapshotOptions`] are `None` but still reported in `formats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardSnapshot {
+    pub formats: AvailableTypes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+    /// base64 encoded png
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// One representation of the clipboard contents, written together with others by [`Clipboard::write_contents`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum ClipboardItemContent {
+    Text(String),
+    Html(String),
+    Rtf(String),
+    /// base64 encoded image or data url, in any format accepted by [`Clipboard::write_image_binary`]
+    Image(String),
+    /// file uris, in the format expected by [`Clipboard::write_files_uris`]
+    Files(Vec<String>),
+    /// raw bytes of a platform specific format, e.g. a mime type on Linux
+    Custom {
+        format: String,
+        data: Vec<u8>,
+    },
+}
+
+/// Everything that was on the clipboard, including formats without a typed API.
+/// Taken by [`Clipboard::save_snapshot`] and written back by [`Clipboard::restore_snapshot`].
+#[derive(Debug, Clone, Default)]
+pub struct SavedClipboard {
+    pub(crate) text: Option<String>,
+    pub(crate) html: Option<String>,
+    pub(crate) rtf: Option<String>,
+    pub(crate) files: Option<Vec<String>>,
+    pub(crate) image: Option<DynamicImage>,
+    /// raw bytes of every other format, by platform format name
+    pub(crate) other: Vec<(String, Vec<u8>)>,
+}
+
+/// Snapshots saved from the webview, which can only refer to them by id.
+#[derive(Default)]
+pub(crate) struct SavedSnapshots {
+    next_id: u64,
+    /// the snapshots by selection and id, with the label of the webview that saved them
+    snapshots: HashMap<(ClipboardSelection, u64), (String, SavedClipboard)>,
+}
+
+/// Which backend the plugin runs on and whether it can be used.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendStatus {
+    pub backend: String,
+    pub available: bool,
+    /// why the backend is unavailable, `None` when it is available
+    pub reason: Option<String>,
+}
+
 /// Access to the clipboard APIs.
+///
+/// The methods work on the regular clipboard, use [`Clipboard::selection`] for the other selections.
 pub struct Clipboard {
-    pub clipboard: Arc<Mutex<ClipboardRsContext>>,
-    pub watcher_shutdown: Arc<Mutex<Option<WatcherShutdown>>>,
+    pub(crate) selection: ClipboardSelection,
+    pub clipboard: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    pub watcher: Arc<Mutex<Option<WatchHandle>>>,
+    /// the app and the webviews that keep the watcher running
+    pub(crate) monitor_users: Arc<Mutex<MonitorUsers>>,
+    pub(crate) monitor_config: MonitorConfig,
+    pub(crate) expiry: Arc<Expiry>,
+    pub(crate) tracker: Arc<Mutex<ChangeTracker>>,
+    pub(crate) seq: Arc<AtomicU64>,
+    pub(crate) subscribers: Subscribers,
+    pub(crate) saved: Arc<Mutex<SavedSnapshots>>,
+    /// the other selections of the platform, only set on the regular clipboard
+    pub(crate) selections: HashMap<ClipboardSelection, Clipboard>,
+    #[cfg(feature = "history")]
+    pub(crate) history: Option<Arc<Mutex<crate::history::History>>>,
 }
 impl Clipboard {
-    pub fn has(&self, format: ContentFormat) -> Result<bool, String> {
-        Ok(self
-            .clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .has(format))
+    /// A clipboard for another selection, sharing event numbering, subscribers and saved snapshots with this one.
+    /// History only records the regular clipboard.
+    fn for_selection(
+        &self,
+        selection: ClipboardSelection,
+        backend: Box<dyn ClipboardBackend>,
+    ) -> Clipboard {
+        Clipboard {
+            selection,
+            clipboard: Arc::new(Mutex::new(backend)),
+            watcher: Arc::default(),
+            monitor_users: Arc::default(),
+            monitor_config: self.monitor_config.clone(),
+            expiry: self.expiry.clone(),
+            tracker: Arc::default(),
+            seq: self.seq.clone(),
+            subscribers: self.subscribers.clone(),
+            saved: self.saved.clone(),
+            selections: HashMap::new(),
+            #[cfg(feature = "history")]
+            history: None,
+        }
+    }
+
+    /// The clipboard of `selection`, e.g. [`ClipboardSelection::Primary`] for middle-click paste on Linux.
+    /// Every read, write and monitor method of the returned clipboard works on that selection.
+    pub fn selection(&self, selection: ClipboardSelection) -> Result<&Clipboard> {
+        if selection == self.selection {
+            return Ok(self);
+        }
+        self.selections
+            .get(&selection)
+            .ok_or_else(|| Error::SelectionUnavailable(selection.name().to_string()))
+    }
+
+    /// Selections this platform has and that could be reached when the plugin started, the regular clipboard first.
+    pub fn available_selections(&self) -> Vec<ClipboardSelection> {
+        [
+            ClipboardSelection::Clipboard,
+            ClipboardSelection::Primary,
+            ClipboardSelection::Secondary,
+        ]
+        .into_iter()
+        .filter(|selection| self.selection(*selection).is_ok())
+        .collect()
     }
 
-    pub fn available_types(&self) -> Result<AvailableTypes, String> {
+    /// Make the contents written by the app outlive it, see [`crate::Builder::persist_on_exit`].
+    /// The plugin calls this when the app exits, after stopping the monitor so handing the contents over is not reported as a change.
+    /// Content still waiting to expire and sensitive text are not handed over, pending expiries are cancelled.
+    /// Such content that is still on its selection is cleared right away, it would outlive the app and its
+    /// expiry otherwise. [`crate::Builder::keep_expiring_on_exit`] leaves all but sensitive text in place,
+    /// sensitive text is cleared even when it was written not to expire.
+    pub fn persist_on_exit(&self, policy: PersistOnExit) -> Result<()> {
+        self.watcher.lock()?.take();
+        let cancelled = self.expiry.cancel_all()?;
+        for (selection, clear) in &cancelled {
+            if let (Some(fingerprint), Ok(clipboard)) = (clear, self.selection(*selection)) {
+                clear_if_unchanged(&clipboard.clipboard, &clipboard.tracker, *fingerprint);
+            }
+        }
+        if cancelled
+            .iter()
+            .any(|(selection, _)| *selection == ClipboardSelection::Clipboard)
+        {
+            return Ok(());
+        }
+        self.clipboard.lock()?.persist_on_exit(policy)
+    }
+
+    /// whether the clipboard backend initialized and can serve requests
+    pub fn is_available(&self) -> bool {
+        self.backend_status()
+            .map(|status| status.available)
+            .unwrap_or(false)
+    }
+
+    pub fn backend_status(&self) -> Result<BackendStatus> {
+        let backend = self.clipboard.lock()?;
+        let reason = backend.check().err().map(|err| match err {
+            Error::BackendUnavailable(reason) => reason,
+            err => err.to_string(),
+        });
+        Ok(BackendStatus {
+            backend: backend.name().to_string(),
+            available: reason.is_none(),
+            reason,
+        })
+    }
+
+    pub fn has(&self, format: ContentFormat) -> Result<bool> {
+        self.clipboard.lock()?.has(format)
+    }
+
+    pub fn available_types(&self) -> Result<AvailableTypes> {
         Ok(AvailableTypes {
             text: self.has(ContentFormat::Text)?,
             html: self.has(ContentFormat::Html)?,
@@ -49,261 +360,608 @@ impl Clipboard {
         })
     }
 
-    pub fn has_text(&self) -> Result<bool, String> {
+    pub fn has_text(&self) -> Result<bool> {
         self.has(ContentFormat::Text)
     }
 
-    pub fn has_rtf(&self) -> Result<bool, String> {
+    pub fn has_rtf(&self) -> Result<bool> {
         self.has(ContentFormat::Rtf)
     }
 
-    pub fn has_image(&self) -> Result<bool, String> {
+    pub fn has_image(&self) -> Result<bool> {
         self.has(ContentFormat::Image)
     }
 
-    pub fn has_html(&self) -> Result<bool, String> {
+    pub fn has_html(&self) -> Result<bool> {
         self.has(ContentFormat::Html)
     }
 
-    pub fn has_files(&self) -> Result<bool, String> {
+    pub fn has_files(&self) -> Result<bool> {
         self.has(ContentFormat::Files)
     }
 
     // Read from Clipboard APIs
 
     /// read text from clipboard
-    pub fn read_text(&self) -> Result<String, String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .get_text()
-            .map_err(|err| err.to_string())
+    pub fn read_text(&self) -> Result<String> {
+        self.clipboard.lock()?.get_text()
     }
 
-    pub fn read_html(&self) -> Result<String, String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .get_html()
-            .map_err(|err| err.to_string())
+    pub fn read_html(&self) -> Result<String> {
+        self.clipboard.lock()?.get_html()
     }
 
-    pub fn read_rtf(&self) -> Result<String, String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .get_rich_text()
-            .map_err(|err| err.to_string())
+    pub fn read_rtf(&self) -> Result<String> {
+        self.clipboard.lock()?.get_rich_text()
     }
 
     /// read files from clipboard and return a `Vec<String>`
     /// Will return a vector of strings, in uri format: `file:///path/to/file`. File path is absolute path.
     /// On Windows, the path will be in the format `C:\\path\\to\\file`. This method is the same as read_files on windows
-    pub fn read_files_uris(&self) -> Result<Vec<String>, String> {
-        let files = self
-            .clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .get_files()
-            .map_err(|err| err.to_string())?;
+    pub fn read_files_uris(&self) -> Result<Vec<String>> {
+        let files = self.clipboard.lock()?.get_files()?;
         Ok(files)
     }
 
     /// read files from clipboard and return a `Vec<String>`
     /// Will return a vector of strings, in absolute path format: `/path/to/file`.
     /// On Windows, the path will be in the format `C:\\path\\to\\file`. This method is the same as read_files_uris on windows
-    pub fn read_files(&self) -> Result<Vec<String>, String> {
+    pub fn read_files(&self) -> Result<Vec<String>> {
         let files = self.read_files_uris()?;
         // iterate through the files and remove the `file://` prefix if there is any. Only remove the prefix if it's in the beginning
         let files_str = files
             .iter()
-            .map(|file| {
-                if file.starts_with("file://") {
-                    file.strip_prefix("file://").unwrap().to_string()
-                } else {
-                    file.to_string()
-                }
-            })
+            .map(|file| file.strip_prefix("file://").unwrap_or(file).to_string())
             .collect::<Vec<_>>();
         Ok(files_str)
     }
 
     /// Write files uris to clipboard. The files should be in uri format: `file:///path/to/file` on Mac and Linux. File path is absolute path.
     /// On Windows, the path should be in the format `C:\\path\\to\\file`.
-    pub fn write_files_uris(&self, files: Vec<String>) -> Result<(), String> {
-        // iterate through files, check if it starts with files://, if not throw error (only linux and mac)
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        {
-            for file in &files {
-                if !file.starts_with("file://") {
-                    return Err(format!(
-                        "Invalid file uri: {}. File uri should start with file://",
-                        file
-                    ));
-                }
-            }
-        }
-        // On Windows, we don't need the file:// prefix, so we remove it if it's there
-        #[cfg(target_os = "windows")]
-        {
-            for file in &files {
-                if file.starts_with("file://") {
-                    return Err(format!(
-                        "Invalid file uri: {}. File uri on Windows should not start with file://",
-                        file
-                    ));
-                }
-            }
-        }
+    pub fn write_files_uris(&self, files: Vec<String>) -> Result<()> {
+        check_file_uris(&files)?;
+        self.write(|clipboard| clipboard.set_files(files))
+    }
 
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set_files(files)
-            .map_err(|err| err.to_string())
+    /// Every raw format on the clipboard, e.g. mime types on Linux, UTIs on macOS or format names on Windows,
+    /// in the order the source app offers them.
+    pub fn available_formats(&self) -> Result<Vec<FormatInfo>> {
+        let clipboard = self.clipboard.lock()?;
+        Ok(clipboard
+            .available_formats()?
+            .into_iter()
+            .map(|name| FormatInfo {
+                mime: normalized_mime(&name),
+                size: clipboard.buffer_size(&name),
+                name,
+            })
+            .collect())
+    }
+
+    /// read the raw bytes of any format listed by [`Clipboard::available_formats`]
+    pub fn read_buffer(&self, format: &str) -> Result<Vec<u8>> {
+        self.clipboard.lock()?.get_buffer(format)
     }
 
     /// read image from clipboard and return a base64 string
-    pub fn read_image_base64(&self) -> Result<String, String> {
+    pub fn read_image_base64(&self) -> Result<String> {
         let image_bytes = self.read_image_binary()?;
         let base64_str = general_purpose::STANDARD.encode(image_bytes);
         Ok(base64_str)
     }
 
     /// read image from clipboard and return a `Vec<u8>`
-    pub fn read_image_binary(&self) -> Result<Vec<u8>, String> {
-        let image = self
-            .clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .get_image()
-            .map_err(|err| err.to_string())?;
-        let bytes = image
-            .to_png()
-            .map_err(|err| err.to_string())?
-            .get_bytes()
-            .to_vec();
-        // let bytes = util::image_data_to_bytes(&image);
-        Ok(bytes)
+    pub fn read_image_binary(&self) -> Result<Vec<u8>> {
+        let image = self.clipboard.lock()?.get_image()?;
+        encode_png(&image)
+    }
+
+    /// Read the clipboard image in the given encoding, e.g. a compact jpeg for previews or raw rgba pixels for editing.
+    pub fn read_image(&self, encoding: ImageEncoding) -> Result<EncodedImage> {
+        let image = self.clipboard.lock()?.get_image()?;
+        encode_image(&image, encoding)
+    }
+
+    /// Dimensions and pixel layout of the clipboard image, without encoding or transferring it.
+    pub fn read_image_info(&self) -> Result<ImageInfo> {
+        let image = self.clipboard.lock()?.get_image()?;
+        image_info(&image)
+    }
+
+    /// Read a preview of the clipboard image that fits in `max_width` x `max_height`, resized with `filter` before encoding.
+    pub fn read_image_thumbnail(
+        &self,
+        max_width: u32,
+        max_height: u32,
+        filter: ThumbnailFilter,
+        encoding: ImageEncoding,
+    ) -> Result<EncodedImage> {
+        let image = self.clipboard.lock()?.get_image()?;
+        encode_image(&thumbnail(image, max_width, max_height, filter)?, encoding)
+    }
+
+    /// Read every available format while holding the clipboard lock once, so the result cannot mix two clipboard states.
+    pub fn snapshot(&self, options: &SnapshotOptions) -> Result<ClipboardSnapshot> {
+        let clipboard = self.clipboard.lock()?;
+        let formats = AvailableTypes {
+            text: clipboard.has(ContentFormat::Text)?,
+            html: clipboard.has(ContentFormat::Html)?,
+            rtf: clipboard.has(ContentFormat::Rtf)?,
+            image: clipboard.has(ContentFormat::Image)?,
+            files: clipboard.has(ContentFormat::Files)?,
+        };
+        let image = (formats.image && options.image)
+            .then(|| clipboard.get_image())
+            .transpose()?;
+        let snapshot = ClipboardSnapshot {
+            text: (formats.text && options.text)
+                .then(|| clipboard.get_text())
+                .transpose()?,
+            html: (formats.html && options.html)
+                .then(|| clipboard.get_html())
+                .transpose()?,
+            rtf: (formats.rtf && options.rtf)
+                .then(|| clipboard.get_rich_text())
+                .transpose()?,
+            files: (formats.files && options.files)
+                .then(|| clipboard.get_files())
+                .transpose()?,
+            image: None,
+            formats,
+        };
+        // encode after releasing the lock, the image is already a copy
+        drop(clipboard);
+        Ok(ClipboardSnapshot {
+            image: image
+                .map(|image| encode_png(&image))
+                .transpose()?
+                .map(|png| general_purpose::STANDARD.encode(png)),
+            ..snapshot
+        })
+    }
+
+    /// [`Clipboard::snapshot`] with every format
+    pub fn read_all(&self) -> Result<ClipboardSnapshot> {
+        self.snapshot(&SnapshotOptions::default())
+    }
+
+    /// Capture everything on the clipboard, so it can be put back after the clipboard was borrowed.
+    /// Formats that cannot be read as raw bytes are left out.
+    pub fn save_snapshot(&self) -> Result<SavedClipboard> {
+        let clipboard = self.clipboard.lock()?;
+        let has = |format: ContentFormat| clipboard.has(format);
+        let mut saved = SavedClipboard {
+            text: has(ContentFormat::Text)?
+                .then(|| clipboard.get_text())
+                .transpose()?,
+            html: has(ContentFormat::Html)?
+                .then(|| clipboard.get_html())
+                .transpose()?,
+            rtf: has(ContentFormat::Rtf)?
+                .then(|| clipboard.get_rich_text())
+                .transpose()?,
+            files: has(ContentFormat::Files)?
+                .then(|| clipboard.get_files())
+                .transpose()?,
+            image: None,
+            other: vec![],
+        };
+        if has(ContentFormat::Image)? {
+            saved.image = Some(
+                clipboard
+                    .get_image()?
+                    .get_dynamic_image()
+                    .map_err(|err| Error::ImageDecode(err.to_string()))?,
+            );
+        }
+        for format in clipboard.available_formats()? {
+            if STANDARD_FORMATS.contains(&format.as_str())
+                || saved.other.iter().any(|(name, _)| *name == format)
+            {
+                continue;
+            }
+            if let Ok(buffer) = clipboard.get_buffer(&format) {
+                saved.other.push((format, buffer));
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Replace the clipboard with a snapshot taken by [`Clipboard::save_snapshot`].
+    pub fn restore_snapshot(&self, saved: &SavedClipboard) -> Result<()> {
+        let mut contents = vec![];
+        if let Some(text) = &saved.text {
+            contents.push(ClipboardContent::Text(text.clone()));
+        }
+        if let Some(html) = &saved.html {
+            contents.push(ClipboardContent::Html(html.clone()));
+        }
+        if let Some(rtf) = &saved.rtf {
+            contents.push(ClipboardContent::Rtf(rtf.clone()));
+        }
+        if let Some(files) = &saved.files {
+            contents.push(ClipboardContent::Files(files.clone()));
+        }
+        if let Some(image) = &saved.image {
+            contents.push(ClipboardContent::Image(RustImageData::from_dynamic_image(
+                image.clone(),
+            )));
+        }
+        for (format, buffer) in &saved.other {
+            contents.push(ClipboardContent::Other(format.clone(), buffer.clone()));
+        }
+        if contents.is_empty() {
+            return self.clear();
+        }
+        self.write(|clipboard| clipboard.set(contents))
+    }
+
+    /// Run `f` and put the clipboard contents from before the call back afterwards, also when `f` fails.
+    /// The error of `f` takes precedence, a restore that fails as well is only logged then.
+    ///
+    /// ```ignore
+    /// clipboard.restore_after(|clipboard| {
+    ///     clipboard.write_text(plain_text)?;
+    ///     paste_into_other_app()
+    /// })?;
+    /// ```
+    pub fn restore_after<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let saved = self.save_snapshot()?;
+        match f(self) {
+            Ok(value) => self.restore_snapshot(&saved).map(|_| value),
+            Err(err) => {
+                if let Err(restore_err) = self.restore_snapshot(&saved) {
+                    log::error!(
+                        "clipboard contents could not be restored after {err}: {restore_err}"
+                    );
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// [`Clipboard::save_snapshot`] for the webview `owner`, the snapshot stays in the plugin until restored or
+    /// discarded. Ids are shared by all webviews and selections, only `owner` can use the returned one and only
+    /// on this selection.
+    pub fn save_snapshot_handle(&self, owner: &str) -> Result<u64> {
+        let snapshot = self.save_snapshot()?;
+        let mut saved = self.saved.lock()?;
+        let id = saved.next_id;
+        saved.next_id += 1;
+        saved
+            .snapshots
+            .insert((self.selection, id), (owner.to_string(), snapshot));
+        Ok(id)
+    }
+
+    /// Restore and forget a snapshot that `owner` saved with [`Clipboard::save_snapshot_handle`].
+    pub fn restore_snapshot_handle(&self, owner: &str, id: u64) -> Result<()> {
+        let snapshot = self.take_snapshot_handle(owner, id, |_| Ok(()))?;
+        self.restore_snapshot(&snapshot)
+    }
+
+    /// [`Clipboard::restore_snapshot_handle`] that fails unless `scope` may write every format of the snapshot,
+    /// which is kept then.
+    pub(crate) fn restore_snapshot_handle_scoped(
+        &self,
+        scope: &ClipboardScope,
+        owner: &str,
+        id: u64,
+    ) -> Result<()> {
+        let snapshot = self.take_snapshot_handle(owner, id, |saved| scope.check_saved(saved))?;
+        self.restore_snapshot(&snapshot)
+    }
+
+    /// Remove the snapshot `id` of `owner` once `check` passed on it.
+    /// Snapshots of other webviews are not found, their ids must not tell what they saved. Neither are
+    /// snapshots of other selections, they would be restored onto the wrong one.
+    fn take_snapshot_handle(
+        &self,
+        owner: &str,
+        id: u64,
+        check: impl FnOnce(&SavedClipboard) -> Result<()>,
+    ) -> Result<SavedClipboard> {
+        let key = (self.selection, id);
+        let mut saved = self.saved.lock()?;
+        match saved.snapshots.get(&key) {
+            Some((saved_by, snapshot)) if saved_by == owner => check(snapshot)?,
+            _ => return Err(Error::SnapshotNotFound(id)),
+        }
+        Ok(saved
+            .snapshots
+            .remove(&key)
+            .map(|(_, snapshot)| snapshot)
+            .unwrap_or_default())
+    }
+
+    /// Forget a snapshot that `owner` saved with [`Clipboard::save_snapshot_handle`] without restoring it.
+    pub fn discard_snapshot_handle(&self, owner: &str, id: u64) -> Result<()> {
+        self.take_snapshot_handle(owner, id, |_| Ok(())).map(|_| ())
     }
 
     // Write to Clipboard APIs
-    pub fn write_text(&self, text: String) -> Result<(), String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set_text(text)
-            .map_err(|err| err.to_string())
+
+    /// Run a write on the backend and remember what it put on the clipboard,
+    /// so the monitor can tell the plugin's own writes apart from external changes.
+    /// The content expires as configured by [`crate::Builder::expire_after`].
+    pub(crate) fn write(
+        &self,
+        write: impl FnOnce(&dyn ClipboardBackend) -> Result<()>,
+    ) -> Result<()> {
+        self.write_expiring(write, self.expiry.after(), false)
     }
 
-    pub fn write_html(&self, html: String) -> Result<(), String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set_html(html)
-            .map_err(|err| err.to_string())
+    /// [`Clipboard::write`] with content that expires after `after`, `None` keeps it.
+    /// `sensitive` content is also cleared when the app exits before it expired, or at all with `None`.
+    fn write_expiring(
+        &self,
+        write: impl FnOnce(&dyn ClipboardBackend) -> Result<()>,
+        after: Option<Duration>,
+        sensitive: bool,
+    ) -> Result<()> {
+        let clipboard = self.clipboard.lock()?;
+        write(clipboard.as_ref())?;
+        // fingerprinted once, only when the monitor or an expiry needs it
+        let fingerprint = if after.is_some() || sensitive || self.tracker.lock()?.active {
+            let fingerprint = content_fingerprint(clipboard.as_ref());
+            self.tracker.lock()?.record_self_write(fingerprint);
+            Some(fingerprint)
+        } else {
+            None
+        };
+        match fingerprint.filter(|_| after.is_some() || sensitive) {
+            Some(fingerprint) => self.expiry.schedule(
+                self.selection,
+                &self.clipboard,
+                &self.tracker,
+                fingerprint,
+                after,
+                sensitive,
+            ),
+            // whatever was waiting to expire has just been replaced
+            None => self.expiry.cancel(self.selection).map(|_| ()),
+        }
     }
 
-    pub fn write_html_and_text(&self, html: String, text: String) -> Result<(), String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set(vec![
+    pub fn write_text(&self, text: String) -> Result<()> {
+        self.write(|clipboard| clipboard.set_text(text))
+    }
+
+    /// Write a password or one-time code. The text carries the platform's hints that keep it out of
+    /// clipboard managers and the system clipboard history, and is cleared after `clear_after`
+    /// (the builder's [`crate::Builder::sensitive_clear_after`] when `None`, zero keeps it until the app exits)
+    /// unless something else was copied in the meantime. [`crate::Builder::expire_after`] caps the delay.
+    pub fn write_sensitive_text(&self, text: String, clear_after: Option<Duration>) -> Result<()> {
+        let mut contents = vec![ClipboardContent::Text(text)];
+        contents.extend(
+            SENSITIVE_HINTS
+                .iter()
+                .map(|(format, data)| ClipboardContent::Other(format.to_string(), data.to_vec())),
+        );
+        self.write_expiring(
+            |clipboard| clipboard.set(contents),
+            self.expiry.sensitive_after(clear_after),
+            true,
+        )
+    }
+
+    pub fn write_html(&self, html: String) -> Result<()> {
+        self.write(|clipboard| clipboard.set_html(html))
+    }
+
+    pub fn write_html_and_text(&self, html: String, text: String) -> Result<()> {
+        self.write(|clipboard| {
+            clipboard.set(vec![
                 ClipboardContent::Text(text),
                 ClipboardContent::Html(html),
             ])
-            .map_err(|err| err.to_string())
+        })
     }
 
-    pub fn write_rtf(&self, rtf: String) -> Result<(), String> {
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set_rich_text(rtf)
-            .map_err(|err| err.to_string())
+    pub fn write_rtf(&self, rtf: String) -> Result<()> {
+        self.write(|clipboard| clipboard.set_rich_text(rtf))
     }
 
-    /// write base64 png image to clipboard
-    pub fn write_image_base64(&self, base64_image: String) -> Result<(), String> {
-        let decoded = general_purpose::STANDARD
-            .decode(base64_image)
-            .map_err(|err| err.to_string())?;
+    /// Write a base64 encoded image to clipboard, a `data:image/...;base64,` url is accepted as well.
+    /// See [`Clipboard::write_image_binary`] for the supported formats.
+    pub fn write_image_base64(&self, base64_image: String) -> Result<()> {
+        let decoded = decode_base64_image(&base64_image)?;
         self.write_image_binary(decoded)
-            .map_err(|err| err.to_string())?;
-        Ok(())
     }
 
-    pub fn write_image_binary(&self, bytes: Vec<u8>) -> Result<(), String> {
-        let img = RustImageData::from_bytes(bytes.as_bytes()).map_err(|err| err.to_string())?;
-        self.clipboard
-            .lock()
-            .map_err(|err| err.to_string())?
-            .set_image(img)
-            .unwrap();
+    /// Write an image file to clipboard. The format is detected from the bytes,
+    /// any format the image crate reads is accepted, e.g. png, jpeg, webp, gif (first frame), bmp, tiff or ico.
+    pub fn write_image_binary(&self, bytes: Vec<u8>) -> Result<()> {
+        let img = decode_image(&bytes)?;
+        self.write(|clipboard| clipboard.set_image(img))
+    }
+
+    /// Put raw bytes on the clipboard under a custom format name, e.g. `application/x-my-app-shape`.
+    /// Use [`Clipboard::write_contents`] to offer them together with a text or image fallback.
+    pub fn write_buffer(&self, format: String, buffer: Vec<u8>) -> Result<()> {
+        self.write(|clipboard| clipboard.set(vec![ClipboardContent::Other(format, buffer)]))
+    }
+
+    /// Replace the clipboard with all `contents` in one go, e.g. text together with its rtf and html versions.
+    /// An empty list clears the clipboard.
+    pub fn write_contents(&self, contents: Vec<ClipboardItemContent>) -> Result<()> {
+        let mut items = Vec::with_capacity(contents.len());
+        for content in contents {
+            items.push(match content {
+                ClipboardItemContent::Text(text) => ClipboardContent::Text(text),
+                ClipboardItemContent::Html(html) => ClipboardContent::Html(html),
+                ClipboardItemContent::Rtf(rtf) => ClipboardContent::Rtf(rtf),
+                ClipboardItemContent::Image(base64_image) => {
+                    ClipboardContent::Image(decode_image(&decode_base64_image(&base64_image)?)?)
+                }
+                ClipboardItemContent::Files(files) => {
+                    check_file_uris(&files)?;
+                    ClipboardContent::Files(files)
+                }
+                ClipboardItemContent::Custom { format, data } => {
+                    ClipboardContent::Other(format, data)
+                }
+            });
+        }
+        if items.is_empty() {
+            return self.clear();
+        }
+        self.write(|clipboard| clipboard.set(items))
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.write_expiring(|clipboard| clipboard.clear(), None, false)
+    }
+
+    /// Keep everything the plugin wrote that is still waiting to expire, on every selection.
+    /// Returns the selections that had pending content.
+    pub fn cancel_expiry(&self) -> Result<Vec<ClipboardSelection>> {
+        Ok(self
+            .expiry
+            .cancel_all()?
+            .into_iter()
+            .map(|(selection, _)| selection)
+            .collect())
+    }
+
+    /// Emit `plugin:clipboard://clipboard-expired` to `app_handle` whenever written content expired.
+    pub(crate) fn emit_expiry_to<R: Runtime>(&self, app_handle: AppHandle<R>) -> Result<()> {
+        self.expiry.set_emitter(Box::new(move |event| {
+            let _ = app_handle.emit(CLIPBOARD_EXPIRED_EVENT, event);
+        }))
+    }
+
+    /// Start monitoring with the default [`MonitorOptions`], change events only report the available formats.
+    pub fn start_monitor<R: Runtime>(&self, app_handle: AppHandle<R>) -> Result<()> {
+        self.start_monitor_with_options(app_handle, MonitorOptions::default())
+    }
+
+    /// Start monitoring, attaching the contents enabled in `options` to every change event.
+    /// Change events reach Rust through [`Clipboard::subscribe`] and [`Clipboard::on_change`], webviews only get
+    /// them once they started the monitor from js themselves.
+    /// Fails with [`Error::MonitorAlreadyRunning`] when it was already started from Rust, a monitor that only
+    /// webviews started keeps running for them and from now on for the app as well.
+    pub fn start_monitor_with_options<R: Runtime>(
+        &self,
+        app_handle: AppHandle<R>,
+        options: MonitorOptions,
+    ) -> Result<()> {
+        let mut users = self.monitor_users.lock()?;
+        if users.app.is_some() {
+            return Err(Error::MonitorAlreadyRunning);
+        }
+        self.start_watcher(&app_handle)?;
+        users.app = Some(options);
         Ok(())
     }
 
-    pub fn clear(&self) -> Result<(), String> {
-        self.clipboard.lock().unwrap().clear().unwrap();
+    /// Start monitoring for `webview`, or join the running monitor. Its change events are sent over `channel` and
+    /// only carry what `scope` may read, each start is undone by one [`Clipboard::stop_monitor_for`].
+    pub(crate) fn start_monitor_for<R: Runtime>(
+        &self,
+        app_handle: AppHandle<R>,
+        webview: Webview<R>,
+        options: MonitorOptions,
+        scope: ClipboardScope,
+        channel: Channel<ClipboardChangeEvent>,
+    ) -> Result<()> {
+        let mut users = self.monitor_users.lock()?;
+        self.start_watcher(&app_handle)?;
+        let label = webview.label().to_string();
+        let listener = match users.webviews.remove(&label) {
+            // the latest start brings the channel and scope of the page the webview shows now
+            Some(listener) => MonitorListener {
+                scope,
+                channel,
+                ..listener
+            },
+            None => MonitorListener::new(webview, scope, channel),
+        };
+        let listener = users.webviews.entry(label).or_insert(listener);
+        listener.options = listener.options.union(&options);
+        listener.starts += 1;
         Ok(())
     }
 
-    pub fn start_monitor<R: Runtime>(&self, app_handle: AppHandle<R>) -> Result<(), String> {
-        let _ = app_handle.emit("plugin:clipboard://clipboard-monitor/status", true);
-        let clipboard = ClipboardMonitor::new(app_handle);
-        let mut watcher: ClipboardWatcherContext<ClipboardMonitor<R>> =
-            ClipboardWatcherContext::new().unwrap();
-        let watcher_shutdown = watcher.add_handler(clipboard).get_shutdown_channel();
-        let mut watcher_shutdown_state = self.watcher_shutdown.lock().unwrap();
-        if (*watcher_shutdown_state).is_some() {
+    /// Start the watcher unless it is running already.
+    fn start_watcher<R: Runtime>(&self, app_handle: &AppHandle<R>) -> Result<()> {
+        let mut watcher_state = self.watcher.lock()?;
+        if (*watcher_state).is_some() {
             return Ok(());
         }
-        *watcher_shutdown_state = Some(watcher_shutdown);
-        std::thread::spawn(move || {
-            watcher.start_watch();
+        let mut monitor = ClipboardMonitor::new(self);
+        let on_change = debounced(self.monitor_config.debounce.clone(), move || {
+            monitor.on_clipboard_change()
         });
+        let watcher = self.clipboard.lock()?.watch(on_change)?;
+        *watcher_state = Some(watcher);
+        self.tracker.lock()?.reset(true);
+        // the status event tells the webview about the regular clipboard only, other selections are queried
+        if self.selection == ClipboardSelection::Clipboard {
+            let _ = app_handle.emit(MONITOR_STATUS_EVENT, true);
+        }
         Ok(())
     }
 
-    pub fn stop_monitor<R: Runtime>(&self, app_handle: AppHandle<R>) -> Result<(), String> {
-        let _ = app_handle.emit("plugin:clipboard://clipboard-monitor/status", false);
-        let mut watcher_shutdown_state = self.watcher_shutdown.lock().unwrap();
-        if let Some(watcher_shutdown) = (*watcher_shutdown_state).take() {
-            watcher_shutdown.stop();
+    /// Stop the monitor, for the app and every webview that started it.
+    pub fn stop_monitor<R: Runtime>(&self, app_handle: AppHandle<R>) -> Result<()> {
+        *self.monitor_users.lock()? = MonitorUsers::default();
+        self.stop_watcher(&app_handle)
+    }
+
+    /// Undo one [`Clipboard::start_monitor_for`] of the webview `label`. The monitor keeps running until every
+    /// webview that started it stopped it, or for good when it was started from Rust.
+    pub(crate) fn stop_monitor_for<R: Runtime>(
+        &self,
+        app_handle: AppHandle<R>,
+        label: &str,
+    ) -> Result<()> {
+        let mut users = self.monitor_users.lock()?;
+        if let Some(listener) = users.webviews.get_mut(label) {
+            listener.starts -= 1;
+            if listener.starts == 0 {
+                users.webviews.remove(label);
+            }
+        }
+        if users.is_empty() {
+            self.stop_watcher(&app_handle)?;
         }
-        *watcher_shutdown_state = None;
         Ok(())
     }
 
-    pub fn is_monitor_running(&self) -> bool {
-        (*self.watcher_shutdown.lock().unwrap()).is_some()
+    fn stop_watcher<R: Runtime>(&self, app_handle: &AppHandle<R>) -> Result<()> {
+        if let Some(watcher) = self.watcher.lock()?.take() {
+            watcher.stop();
+            if self.selection == ClipboardSelection::Clipboard {
+                let _ = app_handle.emit(MONITOR_STATUS_EVENT, false);
+            }
+        }
+        self.tracker.lock()?.reset(false);
+        Ok(())
     }
-}
 
-pub struct ClipboardMonitor<R>
-where
-    R: Runtime,
-{
-    app_handle: tauri::AppHandle<R>,
-}
+    /// Receive clipboard changes in Rust over a channel.
+    /// All subscribers share the watcher started by [`Clipboard::start_monitor`], events only arrive while it is running.
+    pub fn subscribe(&self) -> Result<Subscription> {
+        self.subscribers.subscribe()
+    }
 
-impl<R> ClipboardMonitor<R>
-where
-    R: Runtime,
-{
-    pub fn new(app_handle: tauri::AppHandle<R>) -> Self {
-        Self { app_handle }
+    /// Call `callback` on the monitor thread for every clipboard change, until [`Clipboard::unsubscribe`] is called.
+    pub fn on_change(
+        &self,
+        callback: impl FnMut(&ClipboardChangeEvent) + Send + 'static,
+    ) -> Result<SubscriptionId> {
+        self.subscribers.on_change(callback)
     }
-}
 
-impl<R> ClipboardHandler for ClipboardMonitor<R>
-where
-    R: Runtime,
-{
-    fn on_clipboard_change(&mut self) {
-        let _ = self.app_handle.emit(
-            "plugin:clipboard://clipboard-monitor/update",
-            "clipboard update",
-        );
+    pub fn unsubscribe(&self, id: SubscriptionId) -> Result<()> {
+        self.subscribers.unsubscribe(id)
+    }
+
+    pub fn is_monitor_running(&self) -> bool {
+        self.watcher
+            .lock()
+            .map(|watcher| watcher.is_some())
+            .unwrap_or(false)
     }
 }
@@ -0,0 +1,228 @@
+use crate::desktop::encode_png;
+use crate::{Error, Result};
+use base64::{engine::general_purpose, Engine as _};
+use clipboard_rs::{common::RustImage, RustImageData};
+use image::{
+    codecs::{bmp::BmpEncoder, jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ExtendedColorType, GenericImageView, ImageEncoder,
+};
+use serde::{Deserialize, Serialize};
+
+/// Output format of clipboard image reads.
+///
+/// Webp output is lossless only, the image crate has no lossy webp encoder. Use [`ImageEncoding::Jpeg`]
+/// for lossy output, or [`ImageEncoding::Rgba`] and an encoder of your own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "camelCase")]
+pub enum ImageEncoding {
+    /// lossless and widely supported, but slow and large for screenshots
+    #[default]
+    Png,
+    /// lossy without alpha channel, `quality` from 1 to 100
+    Jpeg {
+        #[serde(default = "default_jpeg_quality")]
+        quality: u8,
+    },
+    /// lossless webp, usually much smaller than png. The image crate has no lossy webp encoder,
+    /// a `quality` is rejected with [`Error::InvalidRequest`] instead of being ignored
+    Webp {
+        #[serde(default)]
+        quality: Option<u8>,
+    },
+    Bmp,
+    /// uncompressed 8 bit rgba pixels, row by row, no header. Use the dimensions of [`EncodedImage`]
+    Rgba,
+}
+
+fn default_jpeg_quality() -> u8 {
+    80
+}
+
+impl ImageEncoding {
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "image/png",
+            ImageEncoding::Jpeg { .. } => "image/jpeg",
+            ImageEncoding::Webp { .. } => "image/webp",
+            ImageEncoding::Bmp => "image/bmp",
+            ImageEncoding::Rgba => "application/octet-stream",
+        }
+    }
+}
+
+/// A clipboard image in the requested [`ImageEncoding`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodedImage {
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl EncodedImage {
+    /// Body of the `read_image` and `read_image_thumbnail` responses: width, height and the byte length of the mime
+    /// type as little endian u32, the mime type, then the image data. The js api decodes it in `decodeEncodedImage`.
+    pub(crate) fn into_response_body(self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(12 + self.mime.len() + self.data.len());
+        body.extend_from_slice(&self.width.to_le_bytes());
+        body.extend_from_slice(&self.height.to_le_bytes());
+        body.extend_from_slice(&(self.mime.len() as u32).to_le_bytes());
+        body.extend_from_slice(self.mime.as_bytes());
+        body.extend_from_slice(&self.data);
+        body
+    }
+}
+
+/// Size and pixel layout of a clipboard image, see [`crate::Clipboard::read_image_info`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    /// pixel layout as named by the image crate, e.g. `rgba8` or `rgb16`
+    pub color_type: String,
+    /// size of the decoded pixels in bytes
+    pub byte_size: u64,
+}
+
+/// Resampling filter for thumbnails, from fastest to best looking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbnailFilter {
+    Nearest,
+    #[default]
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl From<ThumbnailFilter> for FilterType {
+    fn from(filter: ThumbnailFilter) -> Self {
+        match filter {
+            ThumbnailFilter::Nearest => FilterType::Nearest,
+            ThumbnailFilter::Triangle => FilterType::Triangle,
+            ThumbnailFilter::CatmullRom => FilterType::CatmullRom,
+            ThumbnailFilter::Gaussian => FilterType::Gaussian,
+            ThumbnailFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+pub(crate) fn image_info(image: &RustImageData) -> Result<ImageInfo> {
+    let image = decode(image)?;
+    let (width, height) = image.dimensions();
+    let color_type = format!("{:?}", image.color()).to_lowercase();
+    Ok(ImageInfo {
+        width,
+        height,
+        color_type,
+        byte_size: image.as_bytes().len() as u64,
+    })
+}
+
+/// Scale the image down to fit in `max_width` x `max_height`, keeping the aspect ratio. Smaller images are not enlarged.
+pub(crate) fn thumbnail(
+    image: RustImageData,
+    max_width: u32,
+    max_height: u32,
+    filter: ThumbnailFilter,
+) -> Result<RustImageData> {
+    let (width, height) = image.get_size();
+    if width <= max_width && height <= max_height {
+        return Ok(image);
+    }
+    let resized = decode(&image)?.resize(max_width.max(1), max_height.max(1), filter.into());
+    Ok(RustImageData::from_dynamic_image(resized))
+}
+
+pub(crate) fn encode_image(image: &RustImageData, encoding: ImageEncoding) -> Result<EncodedImage> {
+    let (width, height) = image.get_size();
+    let mut data = vec![];
+    match encoding {
+        ImageEncoding::Png => data = encode_png(image)?,
+        ImageEncoding::Rgba => data = decode(image)?.to_rgba8().into_raw(),
+        ImageEncoding::Jpeg { quality } => write(
+            JpegEncoder::new_with_quality(&mut data, quality.clamp(1, 100)),
+            decode(image)?.to_rgb8().as_raw(),
+            (width, height),
+            ExtendedColorType::Rgb8,
+        )?,
+        ImageEncoding::Webp { quality: Some(_) } => {
+            return Err(Error::InvalidRequest(
+                "webp is only encoded lossless, remove the quality".to_string(),
+            ))
+        }
+        ImageEncoding::Webp { quality: None } => write(
+            WebPEncoder::new_lossless(&mut data),
+            decode(image)?.to_rgba8().as_raw(),
+            (width, height),
+            ExtendedColorType::Rgba8,
+        )?,
+        ImageEncoding::Bmp => write(
+            BmpEncoder::new(&mut data),
+            decode(image)?.to_rgba8().as_raw(),
+            (width, height),
+            ExtendedColorType::Rgba8,
+        )?,
+    }
+    Ok(EncodedImage {
+        mime: encoding.mime().to_string(),
+        width,
+        height,
+        data,
+    })
+}
+
+fn decode(image: &RustImageData) -> Result<DynamicImage> {
+    image
+        .get_dynamic_image()
+        .map_err(|err| Error::ImageDecode(err.to_string()))
+}
+
+fn write(
+    encoder: impl ImageEncoder,
+    pixels: &[u8],
+    (width, height): (u32, u32),
+    color: ExtendedColorType,
+) -> Result<()> {
+    encoder
+        .write_image(pixels, width, height, color)
+        .map_err(|err| Error::ImageEncode(err.to_string()))
+}
+
+/// Decode an image file of any format the image crate reads, e.g. png, jpeg, webp, gif (first frame), bmp, tiff or ico.
+pub(crate) fn decode_image(bytes: &[u8]) -> Result<RustImageData> {
+    let format = image::guess_format(bytes).map_err(|_| Error::InvalidImage {
+        format: "unknown".to_string(),
+        reason: "not a recognized image format".to_string(),
+    })?;
+    let image =
+        image::load_from_memory_with_format(bytes, format).map_err(|err| Error::InvalidImage {
+            format: format.to_mime_type().to_string(),
+            reason: err.to_string(),
+        })?;
+    Ok(RustImageData::from_dynamic_image(image))
+}
+
+/// Decode base64 image data, plain or as a `data:image/...;base64,` url.
+pub(crate) fn decode_base64_image(input: &str) -> Result<Vec<u8>> {
+    let input = input.trim();
+    let payload = match input.strip_prefix("data:") {
+        Some(url) => {
+            let (media_type, data) = url
+                .split_once(',')
+                .ok_or_else(|| Error::ImageDecode("data url has no data".to_string()))?;
+            if !media_type.starts_with("image/") || !media_type.ends_with(";base64") {
+                return Err(Error::ImageDecode(format!(
+                    "expected a base64 encoded image data url, got data:{media_type}"
+                )));
+            }
+            data
+        }
+        None => input,
+    };
+    Ok(general_purpose::STANDARD.decode(payload.trim())?)
+}
@@ -1,4 +1,7 @@
-use serde::{ser::Serializer, Serialize};
+use serde::{
+    ser::{SerializeStruct, Serializer},
+    Serialize,
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -9,6 +12,84 @@ pub enum Error {
     #[cfg(mobile)]
     #[error(transparent)]
     PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
+    #[error("clipboard does not contain {0}")]
+    FormatUnavailable(String),
+    #[error("invalid file uri {uri}: {reason}")]
+    InvalidFileUri { uri: String, reason: &'static str },
+    #[error("failed to decode image: {0}")]
+    ImageDecode(String),
+    #[error("failed to decode {format} image: {reason}")]
+    InvalidImage { format: String, reason: String },
+    #[error("failed to encode image: {0}")]
+    ImageEncode(String),
+    #[error(transparent)]
+    Base64Decode(#[from] base64::DecodeError),
+    #[error("invalid command request: {0}")]
+    InvalidRequest(String),
+    #[error("clipboard lock is poisoned")]
+    LockPoisoned,
+    #[error("clipboard backend is unavailable: {0}")]
+    BackendUnavailable(String),
+    #[error("the {0} selection is not available on this platform")]
+    SelectionUnavailable(String),
+    #[error("clipboard monitor is already running")]
+    MonitorAlreadyRunning,
+    #[error("clipboard snapshot {0} does not exist")]
+    SnapshotNotFound(u64),
+    #[error("clipboard history entry {0} does not exist")]
+    HistoryEntryNotFound(u64),
+    #[error("clipboard history store error: {0}")]
+    HistoryStore(String),
+    #[error("{0} is not allowed by the clipboard scope")]
+    ScopeDenied(String),
+    #[error("{size} bytes of {format} exceed the clipboard scope limit of {max} bytes")]
+    PayloadTooLarge { format: String, size: u64, max: u64 },
+    #[error("window {window} may not read the clipboard: {reason}")]
+    AccessDenied {
+        window: String,
+        reason: &'static str,
+    },
+    /// the other side did not answer in time, the call can be retried
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+    #[error("clipboard error: {0}")]
+    Clipboard(String),
+}
+
+impl Error {
+    /// Stable identifier of the error variant, sent to the frontend as `kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            #[cfg(mobile)]
+            Error::PluginInvoke(_) => "pluginInvoke",
+            Error::FormatUnavailable(_) => "formatUnavailable",
+            Error::InvalidFileUri { .. } => "invalidFileUri",
+            Error::ImageDecode(_) => "imageDecode",
+            Error::InvalidImage { .. } => "invalidImage",
+            Error::ImageEncode(_) => "imageEncode",
+            Error::Base64Decode(_) => "base64Decode",
+            Error::InvalidRequest(_) => "invalidRequest",
+            Error::LockPoisoned => "lockPoisoned",
+            Error::BackendUnavailable(_) => "backendUnavailable",
+            Error::SelectionUnavailable(_) => "selectionUnavailable",
+            Error::MonitorAlreadyRunning => "monitorAlreadyRunning",
+            Error::SnapshotNotFound(_) => "snapshotNotFound",
+            Error::HistoryEntryNotFound(_) => "historyEntryNotFound",
+            Error::HistoryStore(_) => "historyStore",
+            Error::ScopeDenied(_) => "scopeDenied",
+            Error::PayloadTooLarge { .. } => "payloadTooLarge",
+            Error::AccessDenied { .. } => "accessDenied",
+            Error::Timeout(_) => "timeout",
+            Error::Clipboard(_) => "clipboard",
+        }
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        Error::LockPoisoned
+    }
 }
 
 impl Serialize for Error {
@@ -16,6 +97,9 @@ impl Serialize for Error {
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
@@ -0,0 +1,405 @@
+use crate::backend::{ClipboardBackend, ClipboardSelection};
+use crate::monitor::{content_fingerprint, ChangeTracker};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const CLIPBOARD_EXPIRED_EVENT: &str = "plugin:clipboard://clipboard-expired";
+
+/// how long sensitive text stays on the clipboard unless the builder or the call says otherwise
+const DEFAULT_SENSITIVE_AFTER: Duration = Duration::from_secs(30);
+
+/// Payload of `plugin:clipboard://clipboard-expired`, emitted after content written by the plugin expired and was cleared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardExpiredEvent {
+    pub selection: ClipboardSelection,
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// How long content written by the plugin stays on the clipboard.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExpiryConfig {
+    /// every write expires after this, `None` keeps writes
+    pub after: Option<Duration>,
+    /// default for `write_sensitive_text`, zero keeps sensitive text until the app exits
+    pub sensitive_after: Option<Duration>,
+    /// leave regular content that still waits to expire on the clipboard when the app exits
+    pub keep_on_exit: bool,
+}
+
+type EmitExpired = Box<dyn Fn(&ClipboardExpiredEvent) + Send>;
+
+/// A write that waits to expire, or sensitive text that is only cleared when the app exits.
+struct Pending {
+    id: u64,
+    /// hash of the written content
+    fingerprint: u64,
+    /// written by `write_sensitive_text`, cleared when the app exits before it expired even with `keep_on_exit`
+    sensitive: bool,
+}
+
+#[derive(Default)]
+struct ExpiryState {
+    next_id: u64,
+    /// the latest write of each selection that waits to expire or is sensitive
+    pending: HashMap<ClipboardSelection, Pending>,
+    emit: Option<EmitExpired>,
+}
+
+/// Clears what the plugin wrote once it expired, unless something else was copied in the meantime.
+/// Shared by all selections, each has at most one pending expiry: the one of its latest write.
+#[derive(Default)]
+pub(crate) struct Expiry {
+    config: ExpiryConfig,
+    state: Mutex<ExpiryState>,
+    /// wakes waiting expiries when theirs was replaced or cancelled
+    changed: Condvar,
+}
+
+impl Expiry {
+    pub fn new(config: ExpiryConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Expiry of a regular write, `None` when writes are kept.
+    pub fn after(&self) -> Option<Duration> {
+        self.config.after
+    }
+
+    /// Expiry of sensitive text: `requested` or the configured default, capped by the expiry of regular writes.
+    pub fn sensitive_after(&self, requested: Option<Duration>) -> Option<Duration> {
+        let after = requested
+            .or(self.config.sensitive_after)
+            .unwrap_or(DEFAULT_SENSITIVE_AFTER);
+        let after = (!after.is_zero()).then_some(after);
+        match (after, self.config.after) {
+            (Some(after), Some(limit)) => Some(after.min(limit)),
+            (after, limit) => after.or(limit),
+        }
+    }
+
+    /// Report expired content through `emit`, e.g. as a tauri event.
+    pub fn set_emitter(&self, emit: EmitExpired) -> Result<()> {
+        self.state.lock()?.emit = Some(emit);
+        Ok(())
+    }
+
+    /// Clear `selection` after `after` if it still holds what was just written there, hashed to `fingerprint`,
+    /// replacing its pending expiry. `sensitive` content is also cleared when the app exits first, even when
+    /// `after` is `None`, see [`Expiry::cancel_all`]. Call it while still holding the backend lock of the write.
+    pub fn schedule(
+        self: &Arc<Self>,
+        selection: ClipboardSelection,
+        clipboard: &Arc<Mutex<Box<dyn ClipboardBackend>>>,
+        tracker: &Arc<Mutex<ChangeTracker>>,
+        fingerprint: u64,
+        after: Option<Duration>,
+        sensitive: bool,
+    ) -> Result<()> {
+        // only a hash of the content is kept, it may be a secret
+        let id = {
+            let mut state = self.state.lock()?;
+            state.next_id += 1;
+            let id = state.next_id;
+            state.pending.insert(
+                selection,
+                Pending {
+                    id,
+                    fingerprint,
+                    sensitive,
+                },
+            );
+            id
+        };
+        self.changed.notify_all();
+        let Some(after) = after else {
+            return Ok(());
+        };
+        let (expiry, clipboard, tracker) = (self.clone(), clipboard.clone(), tracker.clone());
+        thread::spawn(move || {
+            if expiry.wait(selection, id, after) {
+                expiry.expire(selection, &clipboard, &tracker, fingerprint);
+            }
+        });
+        Ok(())
+    }
+
+    /// Drop the pending expiry of `selection`, true when there was one.
+    pub fn cancel(&self, selection: ClipboardSelection) -> Result<bool> {
+        let cancelled = self.state.lock()?.pending.remove(&selection).is_some();
+        self.changed.notify_all();
+        Ok(cancelled)
+    }
+
+    /// Drop every pending expiry and return the selections that had one, together with the fingerprint of
+    /// the content to clear at exit: all of it, or only sensitive content with `keep_on_exit`.
+    pub fn cancel_all(&self) -> Result<Vec<(ClipboardSelection, Option<u64>)>> {
+        let keep = self.config.keep_on_exit;
+        let cancelled = self
+            .state
+            .lock()?
+            .pending
+            .drain()
+            .map(|(selection, pending)| {
+                let clear = pending.sensitive || !keep;
+                (selection, clear.then_some(pending.fingerprint))
+            })
+            .collect();
+        self.changed.notify_all();
+        Ok(cancelled)
+    }
+
+    /// Wait until the expiry `id` is due, false when it was replaced or cancelled first.
+    fn wait(&self, selection: ClipboardSelection, id: u64, after: Duration) -> bool {
+        let deadline = Instant::now() + after;
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        loop {
+            if state.pending.get(&selection).map(|pending| pending.id) != Some(id) {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                state.pending.remove(&selection);
+                return true;
+            }
+            state = match self.changed.wait_timeout(state, deadline - now) {
+                Ok((state, _)) => state,
+                Err(_) => return false,
+            };
+        }
+    }
+
+    fn expire(
+        &self,
+        selection: ClipboardSelection,
+        clipboard: &Mutex<Box<dyn ClipboardBackend>>,
+        tracker: &Mutex<ChangeTracker>,
+        fingerprint: u64,
+    ) {
+        if !clear_if_unchanged(clipboard, tracker, fingerprint) {
+            return;
+        }
+        let event = ClipboardExpiredEvent {
+            selection,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+        };
+        if let Ok(state) = self.state.lock() {
+            if let Some(emit) = &state.emit {
+                emit(&event);
+            }
+        }
+    }
+}
+
+/// Clear the clipboard if it still holds the content hashed to `fingerprint`, true when it was cleared.
+pub(crate) fn clear_if_unchanged(
+    clipboard: &Mutex<Box<dyn ClipboardBackend>>,
+    tracker: &Mutex<ChangeTracker>,
+    fingerprint: u64,
+) -> bool {
+    let Ok(clipboard) = clipboard.lock() else {
+        return false;
+    };
+    if content_fingerprint(clipboard.as_ref()) != fingerprint || clipboard.clear().is_err() {
+        return false;
+    }
+    if let Ok(mut tracker) = tracker.lock() {
+        tracker.record_self_write(content_fingerprint(clipboard.as_ref()));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+    use std::sync::mpsc;
+
+    type Backend = Arc<Mutex<Box<dyn ClipboardBackend>>>;
+
+    const AFTER: Duration = Duration::from_millis(50);
+
+    fn setup() -> (Arc<Expiry>, Backend, Arc<Mutex<ChangeTracker>>) {
+        let backend: Box<dyn ClipboardBackend> = Box::new(MemoryBackend::new());
+        (
+            Arc::new(Expiry::default()),
+            Arc::new(Mutex::new(backend)),
+            Arc::default(),
+        )
+    }
+
+    /// Write `text` and schedule its expiry like the plugin's write methods do.
+    fn write(
+        expiry: &Arc<Expiry>,
+        clipboard: &Backend,
+        tracker: &Arc<Mutex<ChangeTracker>>,
+        text: &str,
+        sensitive: bool,
+    ) {
+        let written = clipboard.lock().unwrap();
+        written.set_text(text.to_string()).unwrap();
+        let selection = ClipboardSelection::Clipboard;
+        expiry
+            .schedule(
+                selection,
+                clipboard,
+                tracker,
+                content_fingerprint(written.as_ref()),
+                Some(AFTER),
+                sensitive,
+            )
+            .unwrap();
+    }
+
+    fn text(clipboard: &Backend) -> Option<String> {
+        clipboard.lock().unwrap().get_text().ok()
+    }
+
+    #[test]
+    fn unchanged_content_is_cleared() {
+        let (expiry, clipboard, tracker) = setup();
+        let (sender, expired) = mpsc::channel();
+        expiry
+            .set_emitter(Box::new(move |event| {
+                let _ = sender.send(event.selection);
+            }))
+            .unwrap();
+        write(&expiry, &clipboard, &tracker, "secret", false);
+        assert_eq!(
+            expired.recv_timeout(AFTER * 10).unwrap(),
+            ClipboardSelection::Clipboard
+        );
+        assert_eq!(text(&clipboard), None);
+    }
+
+    #[test]
+    fn changed_content_is_kept() {
+        let (expiry, clipboard, tracker) = setup();
+        write(&expiry, &clipboard, &tracker, "secret", false);
+        clipboard
+            .lock()
+            .unwrap()
+            .set_text("copied by someone else".to_string())
+            .unwrap();
+        std::thread::sleep(AFTER * 4);
+        assert_eq!(text(&clipboard).as_deref(), Some("copied by someone else"));
+    }
+
+    #[test]
+    fn a_later_write_replaces_the_pending_expiry() {
+        let (expiry, clipboard, tracker) = setup();
+        let (sender, expired) = mpsc::channel();
+        expiry
+            .set_emitter(Box::new(move |event| {
+                let _ = sender.send(event.selection);
+            }))
+            .unwrap();
+        write(&expiry, &clipboard, &tracker, "first", false);
+        write(&expiry, &clipboard, &tracker, "second", false);
+        expired.recv_timeout(AFTER * 10).unwrap();
+        assert!(expired.recv_timeout(AFTER * 4).is_err());
+        assert_eq!(text(&clipboard), None);
+    }
+
+    #[test]
+    fn cancelled_content_is_kept() {
+        let (expiry, clipboard, tracker) = setup();
+        write(&expiry, &clipboard, &tracker, "first", false);
+        assert!(expiry.cancel(ClipboardSelection::Clipboard).unwrap());
+        assert!(!expiry.cancel(ClipboardSelection::Clipboard).unwrap());
+        std::thread::sleep(AFTER * 4);
+        assert_eq!(text(&clipboard).as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn cancel_all_reports_content_to_clear() {
+        let (expiry, clipboard, tracker) = setup();
+        write(&expiry, &clipboard, &tracker, "otp", true);
+        let fingerprint = content_fingerprint(clipboard.lock().unwrap().as_ref());
+        assert_eq!(
+            expiry.cancel_all().unwrap(),
+            [(ClipboardSelection::Clipboard, Some(fingerprint))]
+        );
+        assert!(!clear_if_unchanged(&clipboard, &tracker, fingerprint + 1));
+        assert_eq!(text(&clipboard).as_deref(), Some("otp"));
+        assert!(clear_if_unchanged(&clipboard, &tracker, fingerprint));
+        assert_eq!(text(&clipboard), None);
+        write(&expiry, &clipboard, &tracker, "plain", false);
+        assert!(expiry.cancel_all().unwrap()[0].1.is_some());
+        // only sensitive content is cleared when regular content is kept at exit
+        let expiry = Arc::new(Expiry::new(ExpiryConfig {
+            keep_on_exit: true,
+            ..Default::default()
+        }));
+        write(&expiry, &clipboard, &tracker, "plain", false);
+        write(&expiry, &clipboard, &tracker, "otp", true);
+        let fingerprint = content_fingerprint(clipboard.lock().unwrap().as_ref());
+        assert_eq!(
+            expiry.cancel_all().unwrap(),
+            [(ClipboardSelection::Clipboard, Some(fingerprint))]
+        );
+        write(&expiry, &clipboard, &tracker, "plain", false);
+        assert_eq!(
+            expiry.cancel_all().unwrap(),
+            [(ClipboardSelection::Clipboard, None)]
+        );
+    }
+
+    #[test]
+    fn sensitive_text_without_expiry_is_cleared_at_exit() {
+        let (expiry, clipboard, tracker) = setup();
+        let written = clipboard.lock().unwrap();
+        written.set_text("otp".to_string()).unwrap();
+        let fingerprint = content_fingerprint(written.as_ref());
+        drop(written);
+        let selection = ClipboardSelection::Clipboard;
+        expiry
+            .schedule(selection, &clipboard, &tracker, fingerprint, None, true)
+            .unwrap();
+        std::thread::sleep(AFTER * 4);
+        assert_eq!(text(&clipboard).as_deref(), Some("otp"));
+        assert_eq!(
+            expiry.cancel_all().unwrap(),
+            [(selection, Some(fingerprint))]
+        );
+    }
+
+    #[test]
+    fn regular_expiry_caps_sensitive_text() {
+        let expiry = Expiry::new(ExpiryConfig {
+            after: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        assert_eq!(
+            expiry.sensitive_after(None),
+            Some(DEFAULT_SENSITIVE_AFTER.min(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            expiry.sensitive_after(Some(Duration::from_secs(60))),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            expiry.sensitive_after(Some(Duration::ZERO)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            Expiry::default().sensitive_after(Some(Duration::ZERO)),
+            None
+        );
+    }
+}
@@ -0,0 +1,691 @@
+use crate::desktop::{AvailableTypes, Clipboard, ClipboardItemContent};
+use crate::monitor::{ChangeOrigin, ClipboardChangeEvent};
+use crate::scope::{ClipboardScope, ScopeAccess};
+use crate::{Error, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, Manager, Runtime};
+
+mod search;
+
+use search::SearchIndex;
+
+pub use search::{HistoryFormat, HistoryMatch, HistoryQuery};
+
+/// Where the history is stored and how much of it is kept. Pinned entries are never removed by the limits.
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    /// json file the history is persisted to, defaults to `clipboard-history.json` in the app data dir.
+    /// Images are stored as png files in a directory next to it, e.g. `clipboard-history-images`
+    pub path: Option<PathBuf>,
+    pub max_entries: Option<usize>,
+    pub max_age: Option<Duration>,
+    /// limit on the summed `size` of all entries
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_entries: Some(1000),
+            max_age: None,
+            max_bytes: Some(64 * 1024 * 1024),
+        }
+    }
+}
+
+/// A clipboard change recorded by the monitor, with every format that was on the clipboard.
+/// Images are only loaded by [`Clipboard::history_get`], listed and found entries leave them out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// stable across restarts, unlike `seq`
+    pub id: u64,
+    /// `seq` of the change event that recorded the entry
+    pub seq: u64,
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub origin: ChangeOrigin,
+    pub pinned: bool,
+    pub formats: AvailableTypes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+    /// base64 encoded png, only set by [`Clipboard::history_get`], `formats.image` tells whether there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// bytes taken by the contents, counted against `max_bytes`
+    pub size: u64,
+}
+
+/// A slice of the history, newest entries first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub offset: usize,
+    /// number of entries in the whole history
+    pub total: usize,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryFile {
+    next_id: u64,
+    /// entries without their images, those are stored in files of their own
+    entries: Vec<HistoryEntry>,
+    /// hash of the image of every entry that has one, so a re-copied image is found without loading any
+    #[serde(default)]
+    image_hashes: HashMap<u64, u64>,
+}
+
+/// Work for the writer thread of a history stored on disk.
+enum Write {
+    /// store the image of an entry, taken from [`History::images`]
+    Image(u64),
+    RemoveImage(u64),
+    /// write the json file, only the latest of a burst is written
+    Entries(HistoryFile),
+    /// answered once everything queued before is on disk
+    Flush(mpsc::Sender<()>),
+}
+
+/// The history store, entries are kept newest first. Changes are written to disk by a background thread,
+/// the json file holds the entries and every image is a png file of its own, loaded when its entry is read.
+pub(crate) struct History {
+    /// `None` keeps the history in memory only
+    path: Option<PathBuf>,
+    config: HistoryConfig,
+    data: HistoryFile,
+    /// base64 images that are not in a file, every image of a history kept in memory
+    /// and those the writer did not store yet
+    images: Arc<Mutex<HashMap<u64, String>>>,
+    writer: Option<mpsc::Sender<Write>>,
+    index: SearchIndex,
+}
+
+impl History {
+    pub fn open<R: Runtime>(app: &AppHandle<R>, config: HistoryConfig) -> Result<Self> {
+        let path = match &config.path {
+            Some(path) => path.clone(),
+            None => app
+                .path()
+                .app_data_dir()
+                .map_err(|err| Error::HistoryStore(err.to_string()))?
+                .join("clipboard-history.json"),
+        };
+        Self::load(path, config)
+    }
+
+    /// Read the history stored at `path`, an empty one when there is no file yet.
+    fn load(path: PathBuf, config: HistoryConfig) -> Result<Self> {
+        let data: HistoryFile = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|err| Error::HistoryStore(err.to_string()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        let images = Arc::new(Mutex::new(HashMap::new()));
+        let writer = spawn_writer(path.clone(), images_dir(&path), images.clone())?;
+        let mut history = Self {
+            path: Some(path),
+            config,
+            data,
+            images,
+            writer: Some(writer),
+            index: SearchIndex::default(),
+        };
+        for entry in &history.data.entries {
+            history.index.insert(entry);
+        }
+        history.move_inline_images()?;
+        history.apply_retention();
+        history.persist();
+        Ok(history)
+    }
+
+    /// A history that is never written to disk, used when the stored one cannot be opened so it is not overwritten.
+    pub fn in_memory(config: HistoryConfig) -> Self {
+        Self {
+            path: None,
+            config,
+            data: HistoryFile::default(),
+            images: Default::default(),
+            writer: None,
+            index: SearchIndex::default(),
+        }
+    }
+
+    /// Entries of older stores hold their images inline, hand them to the writer to get files of their own.
+    fn move_inline_images(&mut self) -> Result<()> {
+        let mut images = self.images.lock()?;
+        for entry in &mut self.data.entries {
+            if let Some(image) = entry.image.take() {
+                self.data.image_hashes.insert(entry.id, hash(&image));
+                images.insert(entry.id, image);
+                if let Some(writer) = &self.writer {
+                    let _ = writer.send(Write::Image(entry.id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the contents of a change event. Identical contents already in the history move to the top instead,
+    /// keeping their id and pin, so re-copying an entry does not duplicate it. Sensitive contents are not recorded.
+    pub fn record(&mut self, event: &ClipboardChangeEvent) -> Result<()> {
+        if event.sensitive {
+            return Ok(());
+        }
+        let size = [&event.text, &event.html, &event.rtf, &event.image]
+            .iter()
+            .filter_map(|content| content.as_ref().map(String::len))
+            .chain(event.files.iter().flatten().map(String::len))
+            .sum::<usize>() as u64;
+        let image_hash = event.image.as_deref().map(hash);
+        let mut entry = HistoryEntry {
+            id: self.data.next_id,
+            seq: event.seq,
+            timestamp: event.timestamp,
+            origin: event.origin,
+            pinned: false,
+            formats: event.formats.clone(),
+            text: event.text.clone(),
+            html: event.html.clone(),
+            rtf: event.rtf.clone(),
+            files: event.files.clone(),
+            image: None,
+            size,
+        };
+        let existing = self.data.entries.iter().position(|existing| {
+            existing.text == entry.text
+                && existing.html == entry.html
+                && existing.rtf == entry.rtf
+                && existing.files == entry.files
+                && self.data.image_hashes.get(&existing.id) == image_hash.as_ref()
+        });
+        match existing {
+            Some(index) => {
+                let existing = self.data.entries.remove(index);
+                entry.id = existing.id;
+                entry.pinned = existing.pinned;
+            }
+            None => {
+                self.data.next_id += 1;
+                self.index.insert(&entry);
+                if let (Some(image), Some(image_hash)) = (&event.image, image_hash) {
+                    self.data.image_hashes.insert(entry.id, image_hash);
+                    self.images.lock()?.insert(entry.id, image.clone());
+                    self.send(Write::Image(entry.id));
+                }
+            }
+        }
+        self.data.entries.insert(0, entry);
+        self.apply_retention();
+        self.persist();
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<HistoryEntry> {
+        self.data.entries.clone()
+    }
+
+    pub fn page(&self, offset: usize, limit: usize) -> HistoryPage {
+        HistoryPage {
+            entries: self
+                .data
+                .entries
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+            offset,
+            total: self.data.entries.len(),
+        }
+    }
+
+    /// The entries as far as `scope` may read them, entries without readable contents are left out.
+    pub fn scoped(&self, scope: &ClipboardScope) -> Vec<HistoryEntry> {
+        self.data
+            .entries
+            .iter()
+            .filter_map(|entry| scope.restrict_entry(entry.clone()))
+            .collect()
+    }
+
+    pub fn search(&self, query: &HistoryQuery) -> Vec<HistoryMatch> {
+        search::search(self.data.entries.iter(), &self.index, query)
+    }
+
+    /// The entry with its image, read from its file unless the writer still holds it.
+    pub fn get(&self, id: u64) -> Result<HistoryEntry> {
+        let mut entry = self.data.entries[self.index(id)?].clone();
+        if self.data.image_hashes.contains_key(&id) {
+            entry.image = match self.images.lock()?.get(&id) {
+                Some(image) => Some(image.clone()),
+                None => {
+                    let file = self.path.as_deref().map(|path| image_file(path, id));
+                    let png = fs::read(file.ok_or(Error::HistoryEntryNotFound(id))?)?;
+                    Some(general_purpose::STANDARD.encode(png))
+                }
+            };
+        }
+        Ok(entry)
+    }
+
+    pub fn delete(&mut self, id: u64) -> Result<()> {
+        let index = self.index(id)?;
+        let entry = self.data.entries.remove(index);
+        self.forget(&entry);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn pin(&mut self, id: u64, pinned: bool) -> Result<()> {
+        let index = self.index(id)?;
+        self.data.entries[index].pinned = pinned;
+        self.apply_retention();
+        self.persist();
+        Ok(())
+    }
+
+    /// Wait until every change made so far is on disk.
+    pub fn flush(&self) -> Result<()> {
+        let Some(writer) = &self.writer else {
+            return Ok(());
+        };
+        let (done, flushed) = mpsc::channel();
+        if writer.send(Write::Flush(done)).is_ok() {
+            let _ = flushed.recv();
+        }
+        Ok(())
+    }
+
+    fn index(&self, id: u64) -> Result<usize> {
+        self.data
+            .entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or(Error::HistoryEntryNotFound(id))
+    }
+
+    /// Drop the image of a removed entry.
+    fn forget(&mut self, entry: &HistoryEntry) {
+        self.index.remove(entry.id);
+        if self.data.image_hashes.remove(&entry.id).is_some() {
+            if let Ok(mut images) = self.images.lock() {
+                images.remove(&entry.id);
+            }
+            self.send(Write::RemoveImage(entry.id));
+        }
+    }
+
+    /// Drop the oldest unpinned entries until every configured limit is met.
+    fn apply_retention(&mut self) {
+        let mut removed = Vec::new();
+        if let Some(max_age) = self.config.max_age {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let cutoff = now.saturating_sub(max_age).as_millis() as u64;
+            let (kept, expired) = std::mem::take(&mut self.data.entries)
+                .into_iter()
+                .partition(|entry| entry.pinned || entry.timestamp >= cutoff);
+            self.data.entries = kept;
+            removed = expired;
+        }
+        let max_entries = self.config.max_entries.unwrap_or(usize::MAX);
+        let max_bytes = self.config.max_bytes.unwrap_or(u64::MAX);
+        let mut count = self.data.entries.len();
+        let mut bytes: u64 = self.data.entries.iter().map(|entry| entry.size).sum();
+        // entries are newest first, walk from the back
+        let mut index = self.data.entries.len();
+        while index > 0 && (count > max_entries || bytes > max_bytes) {
+            index -= 1;
+            if !self.data.entries[index].pinned {
+                let entry = self.data.entries.remove(index);
+                count -= 1;
+                bytes -= entry.size;
+                removed.push(entry);
+            }
+        }
+        for entry in removed {
+            self.forget(&entry);
+        }
+    }
+
+    fn send(&self, write: Write) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(write);
+        }
+    }
+
+    /// Queue a write of the json file, the images are already queued by the change itself.
+    fn persist(&self) {
+        if self.writer.is_some() {
+            self.send(Write::Entries(self.data.clone()));
+        }
+    }
+}
+
+/// The contents of `entry` that `scope` may read, an entry without any is denied as a whole.
+fn scoped_entry(scope: &ClipboardScope, entry: HistoryEntry) -> Result<HistoryEntry> {
+    let id = entry.id;
+    scope.restrict_entry(entry).ok_or_else(|| {
+        Error::ScopeDenied(format!("{} history entry {id}", ScopeAccess::Read.verb()))
+    })
+}
+
+fn hash(image: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    image.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Directory of the image files, next to the json file.
+fn images_dir(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-images"))
+}
+
+fn image_file(path: &Path, id: u64) -> PathBuf {
+    images_dir(path).join(format!("{id}.png"))
+}
+
+/// Start the thread that writes the history at `path` to disk. A burst of changes is written with one json write,
+/// failed writes are logged and the next change tries again.
+fn spawn_writer(
+    path: PathBuf,
+    images_dir: PathBuf,
+    images: Arc<Mutex<HashMap<u64, String>>>,
+) -> Result<mpsc::Sender<Write>> {
+    let (writer, writes) = mpsc::channel();
+    let store_image = move |id: u64| -> Result<()> {
+        let Some(image) = images.lock()?.get(&id).cloned() else {
+            return Ok(());
+        };
+        let png = general_purpose::STANDARD
+            .decode(image)
+            .map_err(|err| Error::HistoryStore(err.to_string()))?;
+        fs::create_dir_all(&images_dir)?;
+        write_atomic(&images_dir.join(format!("{id}.png")), &png)?;
+        images.lock()?.remove(&id);
+        Ok(())
+    };
+    thread::Builder::new()
+        .name("clipboard-history".into())
+        .spawn(move || {
+            while let Ok(first) = writes.recv() {
+                let mut entries = None;
+                let mut flushed = Vec::new();
+                for write in std::iter::once(first).chain(writes.try_iter()) {
+                    let written = match write {
+                        Write::Image(id) => store_image(id),
+                        Write::RemoveImage(id) => {
+                            let file = image_file(&path, id);
+                            match fs::remove_file(file) {
+                                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                                    Err(err.into())
+                                }
+                                _ => Ok(()),
+                            }
+                        }
+                        Write::Entries(data) => {
+                            entries = Some(data);
+                            Ok(())
+                        }
+                        Write::Flush(done) => {
+                            flushed.push(done);
+                            Ok(())
+                        }
+                    };
+                    if let Err(err) = written {
+                        log::error!("clipboard history could not be written: {err}");
+                    }
+                }
+                if let Some(data) = entries {
+                    let written = serde_json::to_vec(&data)
+                        .map_err(|err| Error::HistoryStore(err.to_string()))
+                        .and_then(|json| write_atomic(&path, &json));
+                    if let Err(err) = written {
+                        log::error!("clipboard history could not be written: {err}");
+                    }
+                }
+                for done in flushed {
+                    let _ = done.send(());
+                }
+            }
+        })?;
+    Ok(writer)
+}
+
+/// Write next to `path` and rename, so a crash never leaves a truncated file behind.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl Clipboard {
+    pub(crate) fn with_history(mut self, history: History) -> Self {
+        self.history = Some(Arc::new(Mutex::new(history)));
+        self
+    }
+
+    fn history(&self) -> Result<MutexGuard<'_, History>> {
+        match &self.history {
+            Some(history) => Ok(history.lock()?),
+            None => Err(Error::HistoryStore("history is not enabled".into())),
+        }
+    }
+
+    /// every recorded change, newest first
+    pub fn history_list(&self) -> Result<Vec<HistoryEntry>> {
+        Ok(self.history()?.list())
+    }
+
+    pub fn history_page(&self, offset: usize, limit: usize) -> Result<HistoryPage> {
+        Ok(self.history()?.page(offset, limit))
+    }
+
+    /// Search text, the text of html and rtf, and file paths of every entry, best matches first.
+    pub fn history_search(&self, query: &HistoryQuery) -> Result<Vec<HistoryMatch>> {
+        Ok(self.history()?.search(query))
+    }
+
+    pub fn history_get(&self, id: u64) -> Result<HistoryEntry> {
+        self.history()?.get(id)
+    }
+
+    pub fn history_delete(&self, id: u64) -> Result<()> {
+        self.history()?.delete(id)
+    }
+
+    /// Pinned entries are kept regardless of the retention limits.
+    pub fn history_pin(&self, id: u64, pinned: bool) -> Result<()> {
+        self.history()?.pin(id, pinned)
+    }
+
+    /// Wait until the history is written to disk, called when the app exits.
+    pub(crate) fn flush_history(&self) -> Result<()> {
+        match &self.history {
+            Some(history) => history.lock()?.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn scoped_entries(&self, scope: &ClipboardScope) -> Result<Vec<HistoryEntry>> {
+        Ok(self.history()?.scoped(scope))
+    }
+
+    pub(crate) fn history_list_scoped(&self, scope: &ClipboardScope) -> Result<Vec<HistoryEntry>> {
+        self.scoped_entries(scope)
+    }
+
+    pub(crate) fn history_page_scoped(
+        &self,
+        scope: &ClipboardScope,
+        offset: usize,
+        limit: usize,
+    ) -> Result<HistoryPage> {
+        let entries = self.scoped_entries(scope)?;
+        Ok(HistoryPage {
+            total: entries.len(),
+            entries: entries.into_iter().skip(offset).take(limit).collect(),
+            offset,
+        })
+    }
+
+    /// Only searches the contents `scope` may read, so hidden contents cannot be probed with queries.
+    pub(crate) fn history_search_scoped(
+        &self,
+        scope: &ClipboardScope,
+        query: &HistoryQuery,
+    ) -> Result<Vec<HistoryMatch>> {
+        let history = self.history()?;
+        let entries = history.scoped(scope);
+        Ok(search::search(entries.iter(), &history.index, query))
+    }
+
+    pub(crate) fn history_get_scoped(
+        &self,
+        scope: &ClipboardScope,
+        id: u64,
+    ) -> Result<HistoryEntry> {
+        scoped_entry(scope, self.history_get(id)?)
+    }
+
+    /// Only deletes an entry that [`Clipboard::history_get_scoped`] returns.
+    pub(crate) fn history_delete_scoped(&self, scope: &ClipboardScope, id: u64) -> Result<()> {
+        let mut history = self.history()?;
+        scoped_entry(scope, history.get(id)?)?;
+        history.delete(id)
+    }
+
+    /// Only pins an entry that [`Clipboard::history_get_scoped`] returns.
+    pub(crate) fn history_pin_scoped(
+        &self,
+        scope: &ClipboardScope,
+        id: u64,
+        pinned: bool,
+    ) -> Result<()> {
+        let mut history = self.history()?;
+        scoped_entry(scope, history.get(id)?)?;
+        history.pin(id, pinned)
+    }
+
+    /// Every format of a history entry, as written by [`Clipboard::history_copy`].
+    fn history_contents(&self, id: u64) -> Result<Vec<ClipboardItemContent>> {
+        let entry = self.history_get(id)?;
+        let contents = [
+            entry.text.map(ClipboardItemContent::Text),
+            entry.html.map(ClipboardItemContent::Html),
+            entry.rtf.map(ClipboardItemContent::Rtf),
+            entry.files.map(ClipboardItemContent::Files),
+            entry.image.map(ClipboardItemContent::Image),
+        ];
+        Ok(contents.into_iter().flatten().collect())
+    }
+
+    /// Put every format of a history entry back onto the clipboard.
+    pub fn history_copy(&self, id: u64) -> Result<()> {
+        self.write_contents(self.history_contents(id)?)
+    }
+
+    /// [`Clipboard::history_copy`] that fails unless `scope` may write every format of the entry.
+    pub(crate) fn history_copy_scoped(&self, scope: &ClipboardScope, id: u64) -> Result<()> {
+        let contents = self.history_contents(id)?;
+        for content in &contents {
+            scope.check_content(content)?;
+        }
+        self.write_contents(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ClipboardSelection;
+
+    fn event(seq: u64, text: Option<&str>, image: Option<&str>) -> ClipboardChangeEvent {
+        ClipboardChangeEvent {
+            seq,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            origin: ChangeOrigin::External,
+            selection: ClipboardSelection::Clipboard,
+            sensitive: false,
+            formats: AvailableTypes {
+                text: text.is_some(),
+                html: false,
+                rtf: false,
+                image: image.is_some(),
+                files: false,
+            },
+            text: text.map(str::to_string),
+            html: None,
+            rtf: None,
+            files: None,
+            image: image.map(str::to_string),
+        }
+    }
+
+    fn store(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("clipboard-history-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("history.json")
+    }
+
+    #[test]
+    fn images_are_loaded_when_their_entry_is_read() {
+        let path = store("images");
+        let png = general_purpose::STANDARD.encode([1, 2, 3]);
+        let mut history = History::load(path.clone(), HistoryConfig::default()).unwrap();
+        history.record(&event(1, None, Some(&png))).unwrap();
+        history.record(&event(2, Some("text"), None)).unwrap();
+        history.flush().unwrap();
+        let history = History::load(path.clone(), HistoryConfig::default()).unwrap();
+        assert!(history.list().iter().all(|entry| entry.image.is_none()));
+        assert_eq!(history.get(0).unwrap().image, Some(png));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn removed_entries_lose_their_image_file() {
+        let path = store("removed");
+        let png = general_purpose::STANDARD.encode([1, 2, 3]);
+        let mut history = History::load(path.clone(), HistoryConfig::default()).unwrap();
+        history.record(&event(1, None, Some(&png))).unwrap();
+        history.flush().unwrap();
+        assert!(image_file(&path, 0).exists());
+        // re-copying the image keeps its entry
+        history.record(&event(2, None, Some(&png))).unwrap();
+        assert_eq!(history.list().len(), 1);
+        history.delete(0).unwrap();
+        history.flush().unwrap();
+        assert!(!image_file(&path, 0).exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
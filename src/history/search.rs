@@ -0,0 +1,492 @@
+use super::HistoryEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A format a history entry can be filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HistoryFormat {
+    Text,
+    Html,
+    Rtf,
+    Files,
+    Image,
+}
+
+/// Search request for [`crate::Clipboard::history_search`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryQuery {
+    /// words that must all match, as a whole word, a word prefix or a substring. Empty matches every entry
+    pub query: String,
+    /// also accept words within a small edit distance, e.g. typos
+    pub fuzzy: bool,
+    /// only entries containing at least one of these formats, empty for any format
+    pub formats: Vec<HistoryFormat>,
+    /// earliest timestamp in milliseconds since the unix epoch, inclusive
+    pub since: Option<u64>,
+    /// latest timestamp in milliseconds since the unix epoch, inclusive
+    pub until: Option<u64>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            fuzzy: false,
+            formats: vec![],
+            since: None,
+            until: None,
+            offset: 0,
+            limit: 50,
+        }
+    }
+}
+
+/// A matching history entry, results are ordered by descending `score`, newer entries first on ties.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryMatch {
+    pub score: f64,
+    pub entry: HistoryEntry,
+}
+
+/// Searchable words of one entry field, and how much a match in it counts.
+struct Field {
+    format: HistoryFormat,
+    weight: f64,
+    words: Vec<String>,
+    /// the whole field lowercased, for substring matches across word boundaries
+    text: String,
+}
+
+impl Field {
+    fn new(format: HistoryFormat, weight: f64, text: &str) -> Self {
+        let text = text.to_lowercase();
+        Self {
+            format,
+            weight,
+            words: words(&text),
+            text,
+        }
+    }
+}
+
+/// The text, html and rtf fields of every history entry, built once when the entry is recorded
+/// so a query does not convert html and rtf to text again. File paths are short and split per query.
+#[derive(Default)]
+pub(super) struct SearchIndex {
+    fields: HashMap<u64, Vec<Field>>,
+}
+
+impl SearchIndex {
+    pub fn insert(&mut self, entry: &HistoryEntry) {
+        let mut fields = vec![];
+        if let Some(text) = &entry.text {
+            fields.push(Field::new(HistoryFormat::Text, 1.0, text));
+        }
+        if let Some(html) = &entry.html {
+            fields.push(Field::new(HistoryFormat::Html, 0.8, &html_to_text(html)));
+        }
+        if let Some(rtf) = &entry.rtf {
+            fields.push(Field::new(HistoryFormat::Rtf, 0.8, &rtf_to_text(rtf)));
+        }
+        self.fields.insert(entry.id, fields);
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.fields.remove(&id);
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn has_format(entry: &HistoryEntry, format: HistoryFormat) -> bool {
+    match format {
+        HistoryFormat::Text => entry.formats.text,
+        HistoryFormat::Html => entry.formats.html,
+        HistoryFormat::Rtf => entry.formats.rtf,
+        HistoryFormat::Files => entry.formats.files,
+        HistoryFormat::Image => entry.formats.image,
+    }
+}
+
+/// Search `entries` with the fields `index` holds for them. Fields of contents an entry no longer carries,
+/// e.g. left out by a scope, are skipped.
+pub(super) fn search<'a>(
+    entries: impl Iterator<Item = &'a HistoryEntry>,
+    index: &SearchIndex,
+    query: &HistoryQuery,
+) -> Vec<HistoryMatch> {
+    let terms = words(&query.query);
+    let mut matches: Vec<HistoryMatch> = entries
+        .filter(|entry| {
+            query.formats.is_empty()
+                || query
+                    .formats
+                    .iter()
+                    .any(|format| has_format(entry, *format))
+        })
+        .filter(|entry| entry.timestamp >= query.since.unwrap_or(0))
+        .filter(|entry| entry.timestamp <= query.until.unwrap_or(u64::MAX))
+        .filter_map(|entry| {
+            let score = score(entry, index, &terms, query.fuzzy)?;
+            Some(HistoryMatch {
+                score,
+                entry: entry.clone(),
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.entry.timestamp.cmp(&a.entry.timestamp))
+    });
+    matches
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit)
+        .collect()
+}
+
+/// Sum of the best match of every term, `None` when a term matches nowhere.
+fn score(entry: &HistoryEntry, index: &SearchIndex, terms: &[String], fuzzy: bool) -> Option<f64> {
+    if terms.is_empty() {
+        return Some(0.0);
+    }
+    let indexed = index.fields.get(&entry.id).into_iter().flatten();
+    let files: Vec<_> = entry
+        .files
+        .iter()
+        .flatten()
+        .map(|file| Field::new(HistoryFormat::Files, 0.9, file))
+        .collect();
+    let fields: Vec<&Field> = indexed
+        .filter(|field| match field.format {
+            HistoryFormat::Text => entry.text.is_some(),
+            HistoryFormat::Html => entry.html.is_some(),
+            HistoryFormat::Rtf => entry.rtf.is_some(),
+            HistoryFormat::Files | HistoryFormat::Image => false,
+        })
+        .chain(&files)
+        .collect();
+    terms.iter().try_fold(0.0, |total, term| {
+        let best = fields
+            .iter()
+            .map(|field| field.weight * term_score(field, term, fuzzy))
+            .fold(0.0, f64::max);
+        (best > 0.0).then_some(total + best)
+    })
+}
+
+fn term_score(field: &Field, term: &str, fuzzy: bool) -> f64 {
+    let mut best: f64 = 0.0;
+    for word in &field.words {
+        let score = if word == term {
+            1.0
+        } else if word.starts_with(term) {
+            0.8
+        } else if word.contains(term) {
+            0.6
+        } else if fuzzy {
+            fuzzy_score(word, term)
+        } else {
+            0.0
+        };
+        best = best.max(score);
+    }
+    if best == 0.0 && field.text.contains(term) {
+        best = 0.6;
+    }
+    best
+}
+
+/// Score for a word within the allowed edit distance of the term (one edit per four characters).
+fn fuzzy_score(word: &str, term: &str) -> f64 {
+    let term_len = term.chars().count();
+    let allowed = (term_len / 4).max(1);
+    // compare against the word's prefix too, so unfinished words still match
+    let prefix: String = word.chars().take(term_len).collect();
+    let distance = edit_distance(word, term).min(edit_distance(&prefix, term));
+    if distance <= allowed {
+        0.5 * (1.0 - distance as f64 / (term_len + 1) as f64)
+    } else {
+        0.0
+    }
+}
+
+/// Edit distance where swapping two adjacent characters counts as one edit, the most common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// Visible text of an html fragment, good enough for matching words, not for display.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let lower = rest.get(..7).unwrap_or(rest).to_ascii_lowercase();
+        // drop script and style bodies entirely
+        let skip_to = if lower.starts_with("<script") {
+            "</script>"
+        } else if lower.starts_with("<style") {
+            "</style>"
+        } else {
+            ">"
+        };
+        let end = if skip_to == ">" {
+            rest.find('>')
+        } else {
+            rest.to_ascii_lowercase().find(skip_to)
+        };
+        rest = end.map_or("", |end| &rest[end + skip_to.len()..]);
+        text.push(' ');
+    }
+    text.push_str(rest);
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Text of an rtf document without control words and destination groups like font tables.
+pub(crate) fn rtf_to_text(rtf: &str) -> String {
+    let mut text = String::with_capacity(rtf.len());
+    let mut chars = rtf.chars().peekable();
+    // nesting depth of the group being skipped, if any
+    let mut depth = 0usize;
+    let mut skip_from: Option<usize> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                depth += 1;
+                if skip_from.is_none() && chars.peek() == Some(&'\\') {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    let word: String = lookahead
+                        .take_while(|c| c.is_ascii_alphabetic() || *c == '*')
+                        .collect();
+                    if matches!(
+                        word.as_str(),
+                        "*" | "fonttbl" | "colortbl" | "stylesheet" | "info" | "pict"
+                    ) {
+                        skip_from = Some(depth);
+                    }
+                }
+            }
+            '}' => {
+                if skip_from == Some(depth) {
+                    skip_from = None;
+                }
+                depth = depth.saturating_sub(1);
+            }
+            '\\' => match chars.peek().copied() {
+                Some('\'') => {
+                    chars.next();
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if skip_from.is_none() {
+                        if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                            text.push(byte as char);
+                        }
+                    }
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut word = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                        word.push(c);
+                    }
+                    while chars.next_if(|c| c.is_ascii_digit() || *c == '-').is_some() {}
+                    chars.next_if_eq(&' ');
+                    if skip_from.is_none() && matches!(word.as_str(), "par" | "line" | "tab") {
+                        text.push(' ');
+                    }
+                }
+                Some(c) => {
+                    chars.next();
+                    if skip_from.is_none() && matches!(c, '\\' | '{' | '}') {
+                        text.push(c);
+                    }
+                }
+                None => {}
+            },
+            '\r' | '\n' => {}
+            c if skip_from.is_none() => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop::AvailableTypes;
+    use crate::monitor::ChangeOrigin;
+
+    fn entry(id: u64, text: Option<&str>, html: Option<&str>, files: &[&str]) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            seq: id,
+            timestamp: id * 1000,
+            origin: ChangeOrigin::External,
+            pinned: false,
+            formats: AvailableTypes {
+                text: text.is_some(),
+                html: html.is_some(),
+                rtf: false,
+                image: false,
+                files: !files.is_empty(),
+            },
+            text: text.map(str::to_string),
+            html: html.map(str::to_string),
+            rtf: None,
+            files: (!files.is_empty()).then(|| files.iter().map(|file| file.to_string()).collect()),
+            image: None,
+            size: 0,
+        }
+    }
+
+    fn query(query: &str, fuzzy: bool) -> HistoryQuery {
+        HistoryQuery {
+            query: query.to_string(),
+            fuzzy,
+            ..Default::default()
+        }
+    }
+
+    fn find(entries: &[HistoryEntry], query: &HistoryQuery) -> Vec<HistoryMatch> {
+        let mut index = SearchIndex::default();
+        entries.iter().for_each(|entry| index.insert(entry));
+        search(entries.iter(), &index, query)
+    }
+
+    fn ids(matches: &[HistoryMatch]) -> Vec<u64> {
+        matches.iter().map(|found| found.entry.id).collect()
+    }
+
+    #[test]
+    fn exact_words_rank_above_prefixes_and_substrings() {
+        let entries = [
+            entry(1, Some("reporting tools"), None, &[]),
+            entry(2, Some("the report"), None, &[]),
+            entry(3, Some("misreported"), None, &[]),
+            entry(4, Some("unrelated"), None, &[]),
+        ];
+        let found = find(&entries, &query("Report", false));
+        assert_eq!(ids(&found), [2, 1, 3]);
+        assert!(found[0].score > found[1].score && found[1].score > found[2].score);
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        let entries = [
+            entry(1, Some("hello world"), None, &[]),
+            entry(2, Some("hello there"), None, &[]),
+        ];
+        assert_eq!(ids(&find(&entries, &query("hello world", false))), [1]);
+        // an empty query matches everything, newest first
+        assert_eq!(ids(&find(&entries, &query("", false))), [2, 1]);
+    }
+
+    #[test]
+    fn text_outweighs_html_and_files() {
+        let entries = [
+            entry(1, None, Some("<p>budget</p>"), &[]),
+            entry(2, Some("budget"), None, &[]),
+            entry(3, None, None, &["/home/me/budget.xlsx"]),
+        ];
+        assert_eq!(ids(&find(&entries, &query("budget", false))), [2, 3, 1]);
+    }
+
+    #[test]
+    fn fuzzy_accepts_typos() {
+        let entries = [entry(1, Some("quarterly report"), None, &[])];
+        assert!(find(&entries, &query("quartelry", false)).is_empty());
+        let found = find(&entries, &query("quartelry", true));
+        assert_eq!(ids(&found), [1]);
+        assert!(found[0].score < 0.5);
+        assert_eq!(edit_distance("quartelry", "quarterly"), 1);
+    }
+
+    #[test]
+    fn filters_and_pages() {
+        let entries = [
+            entry(1, Some("one"), None, &[]),
+            entry(2, None, Some("<b>two</b>"), &[]),
+            entry(3, Some("three"), None, &[]),
+        ];
+        let html = HistoryQuery {
+            formats: vec![HistoryFormat::Html],
+            ..Default::default()
+        };
+        assert_eq!(ids(&find(&entries, &html)), [2]);
+        let window = HistoryQuery {
+            since: Some(2000),
+            until: Some(3000),
+            ..Default::default()
+        };
+        assert_eq!(ids(&find(&entries, &window)), [3, 2]);
+        let page = HistoryQuery {
+            offset: 1,
+            limit: 1,
+            ..Default::default()
+        };
+        assert_eq!(ids(&find(&entries, &page)), [2]);
+    }
+
+    #[test]
+    fn contents_left_out_are_not_searched() {
+        let mut index = SearchIndex::default();
+        let indexed = entry(1, Some("memo"), Some("<p>secret</p>"), &[]);
+        index.insert(&indexed);
+        let mut scoped = indexed.clone();
+        scoped.html = None;
+        assert!(search([&scoped].into_iter(), &index, &query("secret", false)).is_empty());
+        index.remove(1);
+        assert!(search([&indexed].into_iter(), &index, &query("memo", false)).is_empty());
+    }
+
+    #[test]
+    fn html_to_text_keeps_visible_text() {
+        let text = html_to_text(
+            "<style>p { color: red }</style><p>Fish &amp; chips</p><SCRIPT>alert(1)</SCRIPT>&lt;ok&gt;",
+        );
+        assert_eq!(words(&text), ["fish", "chips", "ok"]);
+        assert!(text.contains("Fish & chips"));
+        assert!(!text.contains("color") && !text.contains("alert"));
+    }
+
+    #[test]
+    fn rtf_to_text_drops_control_words_and_tables() {
+        let rtf = r"{\rtf1\ansi{\fonttbl{\f0 Arial;}}{\colortbl;\red255\green0\blue0;}{\*\generator Word}\f0\fs24 Caf\'e9 \b bold\b0\par braces \{ \}}";
+        assert_eq!(rtf_to_text(rtf), "Café bold braces { }");
+    }
+}
@@ -1,61 +1,305 @@
 pub use models::*;
 use tauri::{
-    plugin::{Builder, TauriPlugin},
+    plugin::{Builder as PluginBuilder, TauriPlugin},
     Manager, Runtime,
 };
 
+#[cfg(desktop)]
+mod backend;
 mod commands;
 #[cfg(desktop)]
+mod debounce;
+#[cfg(desktop)]
 mod desktop;
+#[cfg(desktop)]
+mod encoding;
 mod error;
+#[cfg(desktop)]
+mod expiry;
+#[cfg(all(desktop, feature = "history"))]
+mod history;
 #[cfg(mobile)]
 mod mobile;
 mod models;
+#[cfg(desktop)]
+mod monitor;
+#[cfg(desktop)]
+mod policy;
+#[cfg(desktop)]
+mod scope;
 pub mod utils;
 pub use error::{Error, Result};
 
+#[cfg(all(desktop, unix, not(any(target_os = "macos", target_os = "emscripten"))))]
+pub use backend::WaylandBackend;
+#[cfg(desktop)]
+pub use backend::{
+    ClipboardBackend, ClipboardRsBackend, ClipboardSelection, MemoryBackend, PersistOnExit,
+    WatchHandle,
+};
 #[cfg(desktop)]
-pub use desktop::Clipboard;
+pub use debounce::DebounceEdge;
+#[cfg(desktop)]
+pub use desktop::{
+    Clipboard, ClipboardItemContent, ClipboardSnapshot, FormatInfo, SavedClipboard, SnapshotOptions,
+};
+#[cfg(desktop)]
+pub use encoding::{EncodedImage, ImageEncoding, ImageInfo, ThumbnailFilter};
+#[cfg(desktop)]
+pub use expiry::ClipboardExpiredEvent;
+#[cfg(all(desktop, feature = "history"))]
+pub use history::{
+    HistoryConfig, HistoryEntry, HistoryFormat, HistoryMatch, HistoryPage, HistoryQuery,
+};
 #[cfg(mobile)]
 pub use mobile::Clipboard;
+#[cfg(desktop)]
+pub use monitor::{
+    ChangeOrigin, ClipboardChangeEvent, MonitorOptions, SelfWritePolicy, Subscription,
+    SubscriptionId,
+};
+#[cfg(desktop)]
+pub use policy::ReadPolicy;
+#[cfg(desktop)]
+pub use scope::{ScopeAccess, ScopeEntry};
+
+/// Builder for the clipboard plugin.
+#[derive(Default)]
+pub struct Builder {
+    #[cfg(desktop)]
+    backend: Option<Box<dyn ClipboardBackend>>,
+    #[cfg(desktop)]
+    monitor: monitor::MonitorConfig,
+    #[cfg(desktop)]
+    persist_on_exit: PersistOnExit,
+    #[cfg(desktop)]
+    expiry: expiry::ExpiryConfig,
+    #[cfg(desktop)]
+    read_policy: ReadPolicy,
+    #[cfg(all(desktop, feature = "history"))]
+    history: HistoryConfig,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `backend` instead of the system clipboard, e.g. [`MemoryBackend`] for headless tests.
+    #[cfg(desktop)]
+    pub fn backend<B: ClipboardBackend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Box::new(backend));
+        self
+    }
+
+    /// Drop monitor events whose content is identical to the previous event. Enabled by default.
+    #[cfg(desktop)]
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.monitor.deduplicate = deduplicate;
+        self
+    }
+
+    /// Whether monitor events caused by this plugin's own writes are tagged or dropped. Tagged by default.
+    #[cfg(desktop)]
+    pub fn self_writes(mut self, policy: SelfWritePolicy) -> Self {
+        self.monitor.self_writes = policy;
+        self
+    }
+
+    /// Coalesce bursts of clipboard notifications (e.g. an app setting text, then html, then rtf) into one monitor event.
+    /// A burst ends once no notification arrived for `interval`, `edge` picks when its event is emitted.
+    #[cfg(desktop)]
+    pub fn debounce(mut self, interval: std::time::Duration, edge: DebounceEdge) -> Self {
+        self.monitor.debounce.interval = interval;
+        self.monitor.debounce.edge = edge;
+        self
+    }
+
+    /// Emit at most `events` monitor events per second, later changes are delayed and coalesced. Zero removes the cap.
+    #[cfg(desktop)]
+    pub fn max_events_per_second(mut self, events: u32) -> Self {
+        self.monitor.debounce.min_gap =
+            (events > 0).then(|| std::time::Duration::from_secs(1) / events);
+        self
+    }
+
+    /// What happens to clipboard contents written by the app when it exits.
+    /// Only matters on X11, defaults to handing them to the clipboard manager. Wayland has no such hand-over.
+    #[cfg(desktop)]
+    pub fn persist_on_exit(mut self, policy: PersistOnExit) -> Self {
+        self.persist_on_exit = policy;
+        self
+    }
+
+    /// Clear everything the plugin writes after `after`, unless something else was copied in the meantime.
+    /// `plugin:clipboard://clipboard-expired` is emitted when content expired. Off by default.
+    #[cfg(desktop)]
+    pub fn expire_after(mut self, after: std::time::Duration) -> Self {
+        self.expiry.after = Some(after);
+        self
+    }
+
+    /// Leave content that still waits to expire on the clipboard when the app exits, instead of clearing it.
+    /// It is not handed to the clipboard manager either. Sensitive text is cleared regardless.
+    #[cfg(desktop)]
+    pub fn keep_expiring_on_exit(mut self, keep: bool) -> Self {
+        self.expiry.keep_on_exit = keep;
+        self
+    }
+
+    /// How long text written by `write_sensitive_text` stays on the clipboard, 30 seconds by default.
+    /// Zero keeps it until the app exits.
+    #[cfg(desktop)]
+    pub fn sensitive_clear_after(mut self, clear_after: std::time::Duration) -> Self {
+        self.expiry.sensitive_after = Some(clear_after);
+        self
+    }
+
+    /// Which windows may read the clipboard, e.g. only the focused one or only after a user gesture.
+    /// Every window may read by default.
+    #[cfg(desktop)]
+    pub fn read_policy(mut self, policy: ReadPolicy) -> Self {
+        self.read_policy = policy;
+        self
+    }
+
+    /// Where the clipboard history is stored and which retention limits apply.
+    #[cfg(all(desktop, feature = "history"))]
+    pub fn history(mut self, config: HistoryConfig) -> Self {
+        self.history = config;
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        #[cfg(desktop)]
+        let backend = self.backend;
+        #[cfg(desktop)]
+        let monitor_config = self.monitor;
+        #[cfg(desktop)]
+        let persist_on_exit = self.persist_on_exit;
+        #[cfg(desktop)]
+        let expiry_config = self.expiry;
+        #[cfg(desktop)]
+        let read_policy = self.read_policy;
+        #[cfg(all(desktop, feature = "history"))]
+        let history_config = self.history;
+        let builder = PluginBuilder::new("clipboard")
+            .invoke_handler(tauri::generate_handler![
+                commands::stop_monitor,
+                commands::start_monitor,
+                commands::is_monitor_running,
+                commands::is_available,
+                commands::backend_status,
+                commands::available_selections,
+                commands::has_text,
+                commands::has_image,
+                commands::has_html,
+                commands::has_rtf,
+                commands::has_files,
+                commands::available_types,
+                commands::available_formats,
+                commands::read_buffer,
+                commands::read_all,
+                commands::save_snapshot,
+                commands::read_text,
+                commands::read_files,
+                commands::read_files_uris,
+                commands::read_html,
+                commands::read_image_base64,
+                commands::read_image_binary,
+                commands::read_image,
+                commands::read_image_info,
+                commands::read_image_thumbnail,
+                commands::read_rtf,
+                commands::report_user_gesture,
+                commands::write_text,
+                commands::write_sensitive_text,
+                commands::write_html,
+                commands::write_html_and_text,
+                commands::write_rtf,
+                commands::write_image_binary,
+                commands::write_image_base64,
+                commands::write_files_uris,
+                commands::write_files,
+                commands::write_buffer,
+                commands::write_contents,
+                commands::restore_snapshot,
+                commands::discard_snapshot,
+                commands::clear,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_list,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_page,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_search,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_get,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_delete,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_pin,
+                #[cfg(all(desktop, feature = "history"))]
+                commands::history_copy
+            ])
+            .setup(move |app, api| {
+                #[cfg(mobile)]
+                let clipboard = mobile::init(app, api)?;
+                #[cfg(desktop)]
+                let clipboard = desktop::init(api, backend, monitor_config, expiry_config)?;
+                #[cfg(desktop)]
+                clipboard.emit_expiry_to(app.clone())?;
+                #[cfg(all(desktop, feature = "history"))]
+                let clipboard = clipboard.with_history(
+                    history::History::open(app, history_config.clone()).unwrap_or_else(|err| {
+                        // a broken store must not keep the app from starting, nor be overwritten
+                        log::error!("clipboard history could not be opened, it is kept in memory only: {err}");
+                        history::History::in_memory(history_config)
+                    }),
+                );
+                app.manage(clipboard);
+                app.manage(scope::PathScopes::default());
+                #[cfg(desktop)]
+                app.manage(policy::AccessPolicy::new(read_policy));
+                Ok(())
+            });
+        #[cfg(desktop)]
+        let builder = builder.on_event(move |app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Some(clipboard) = app.try_state::<Clipboard>() {
+                    if let Err(err) = clipboard.persist_on_exit(persist_on_exit) {
+                        log::warn!("clipboard contents could not be kept after exit: {err}");
+                    }
+                    #[cfg(feature = "history")]
+                    if let Err(err) = clipboard.flush_history() {
+                        log::warn!("clipboard history could not be written before exit: {err}");
+                    }
+                }
+            }
+        });
+        builder.build()
+    }
+}
+
+/// Keep serving the clipboard when this process was started for [`PersistOnExit::ClipboardManagerOrServe`], then
+/// exit. Returns right away otherwise, and does nothing on other platforms than Linux.
+///
+/// Call it on the first line of `main` when the app uses that policy, before anything else starts:
+///
+/// ```ignore
+/// fn main() {
+///     tauri_plugin_clipboard::serve_clipboard_if_requested();
+///     tauri::Builder::default()
+///         // ...
+/// }
+/// ```
+///
+/// Without the call the policy only hands the contents to a clipboard manager.
+pub fn serve_clipboard_if_requested() {
+    #[cfg(desktop)]
+    backend::serve_if_requested();
+}
 
 /// Initializes the plugin.
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("clipboard")
-        .invoke_handler(tauri::generate_handler![
-            commands::stop_monitor,
-            commands::start_monitor,
-            commands::is_monitor_running,
-            commands::has_text,
-            commands::has_image,
-            commands::has_html,
-            commands::has_rtf,
-            commands::has_files,
-            commands::available_types,
-            commands::read_text,
-            commands::read_files,
-            commands::read_files_uris,
-            commands::read_html,
-            commands::read_image_base64,
-            commands::read_image_binary,
-            commands::read_rtf,
-            commands::write_text,
-            commands::write_html,
-            commands::write_html_and_text,
-            commands::write_rtf,
-            commands::write_image_binary,
-            commands::write_image_base64,
-            commands::write_files_uris,
-            commands::write_files,
-            commands::clear
-        ])
-        .setup(|app, api| {
-            #[cfg(mobile)]
-            let clipboard = mobile::init(app, api)?;
-            #[cfg(desktop)]
-            let clipboard = desktop::init(api)?;
-            app.manage(clipboard);
-            Ok(())
-        })
-        .build()
+    Builder::new().build()
 }
@@ -0,0 +1,568 @@
+use crate::backend::{
+    is_sensitive, normalized_mime, ClipboardBackend, ClipboardSelection, STANDARD_FORMATS,
+};
+use crate::debounce::DebounceConfig;
+use crate::desktop::{encode_png, AvailableTypes, Clipboard};
+use crate::policy::check_events;
+use crate::scope::ClipboardScope;
+use crate::Result;
+use base64::{engine::general_purpose, Engine as _};
+use clipboard_rs::{common::RustImage, ClipboardHandler, ContentFormat, RustImageData};
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::{ipc::Channel, Runtime, Webview};
+
+pub const MONITOR_STATUS_EVENT: &str = "plugin:clipboard://clipboard-monitor/status";
+
+/// Which contents the monitor reads and attaches to each [`ClipboardChangeEvent`].
+/// Formats that are not enabled are still reported in `formats`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MonitorOptions {
+    pub text: bool,
+    pub html: bool,
+    pub rtf: bool,
+    pub files: bool,
+    /// attach the image as base64 encoded png, this is expensive for large images
+    pub image: bool,
+}
+
+impl MonitorOptions {
+    /// Every content enabled in `self` or `other`.
+    pub(crate) fn union(&self, other: &Self) -> Self {
+        Self {
+            text: self.text || other.text,
+            html: self.html || other.html,
+            rtf: self.rtf || other.rtf,
+            files: self.files || other.files,
+            image: self.image || other.image,
+        }
+    }
+
+    /// Remove the contents of `event` that are not enabled.
+    fn apply(&self, mut event: ClipboardChangeEvent) -> ClipboardChangeEvent {
+        event.text = event.text.filter(|_| self.text);
+        event.html = event.html.filter(|_| self.html);
+        event.rtf = event.rtf.filter(|_| self.rtf);
+        event.files = event.files.filter(|_| self.files);
+        event.image = event.image.filter(|_| self.image);
+        event
+    }
+}
+
+/// A change seen by the monitor, a consistent snapshot of the clipboard taken right after it changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardChangeEvent {
+    /// increases by one for every change observed since the app started
+    pub seq: u64,
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub origin: ChangeOrigin,
+    /// the selection that changed, only the regular clipboard unless another selection's monitor was started
+    pub selection: ClipboardSelection,
+    /// the content carries a password manager hint, e.g. from [`crate::Clipboard::write_sensitive_text`].
+    /// The history skips it.
+    pub sensitive: bool,
+    pub formats: AvailableTypes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+    /// base64 encoded png
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// Identifies a subscription created by [`crate::Clipboard::subscribe`] or [`crate::Clipboard::on_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type ChangeCallback = Arc<Mutex<Box<dyn FnMut(&ClipboardChangeEvent) + Send>>>;
+
+#[derive(Clone)]
+enum Subscriber {
+    Channel(mpsc::Sender<ClipboardChangeEvent>),
+    Callback(ChangeCallback),
+}
+
+/// Rust-side listeners of the monitor, shared by every subscriber and the single watcher started by `start_monitor`.
+#[derive(Clone, Default)]
+pub(crate) struct Subscribers {
+    next_id: Arc<AtomicU64>,
+    list: Arc<Mutex<Vec<(SubscriptionId, Subscriber)>>>,
+}
+
+impl Subscribers {
+    fn add(&self, subscriber: Subscriber) -> Result<SubscriptionId> {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.list.lock()?.push((id, subscriber));
+        Ok(id)
+    }
+
+    pub fn subscribe(&self) -> Result<Subscription> {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.add(Subscriber::Channel(sender))?;
+        Ok(Subscription {
+            id,
+            receiver,
+            subscribers: self.clone(),
+        })
+    }
+
+    pub fn on_change(
+        &self,
+        callback: impl FnMut(&ClipboardChangeEvent) + Send + 'static,
+    ) -> Result<SubscriptionId> {
+        self.add(Subscriber::Callback(Arc::new(Mutex::new(Box::new(
+            callback,
+        )))))
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) -> Result<()> {
+        self.list
+            .lock()?
+            .retain(|(subscriber_id, _)| *subscriber_id != id);
+        Ok(())
+    }
+
+    fn dispatch(&self, event: &ClipboardChangeEvent) {
+        // call subscribers without holding the list, so callbacks may subscribe or unsubscribe
+        let Ok(list) = self.list.lock().map(|list| list.clone()) else {
+            return;
+        };
+        for (id, subscriber) in list {
+            match subscriber {
+                Subscriber::Channel(sender) => {
+                    if sender.send(event.clone()).is_err() {
+                        let _ = self.unsubscribe(id);
+                    }
+                }
+                Subscriber::Callback(callback) => {
+                    if let Ok(mut callback) = callback.lock() {
+                        callback(event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Receives every [`ClipboardChangeEvent`] while the monitor is running. Dropping it unsubscribes.
+pub struct Subscription {
+    id: SubscriptionId,
+    receiver: mpsc::Receiver<ClipboardChangeEvent>,
+    subscribers: Subscribers,
+}
+
+impl Subscription {
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Block until the next change.
+    pub fn recv(&self) -> Option<ClipboardChangeEvent> {
+        self.receiver.recv().ok()
+    }
+
+    pub fn try_recv(&self) -> Option<ClipboardChangeEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<ClipboardChangeEvent> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    pub fn iter(&self) -> mpsc::Iter<'_, ClipboardChangeEvent> {
+        self.receiver.iter()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.subscribers.unsubscribe(self.id);
+    }
+}
+
+/// What the monitor does with changes caused by this plugin's own write calls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelfWritePolicy {
+    /// emit them with `origin: "plugin"`
+    #[default]
+    Tag,
+    /// do not emit them at all
+    Suppress,
+}
+
+/// Where a clipboard change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeOrigin {
+    /// another application, or the user
+    External,
+    /// a write call of this plugin, from Rust or the webview
+    Plugin,
+}
+
+/// Monitor behaviour configured on [`crate::Builder`].
+#[derive(Debug, Clone)]
+pub(crate) struct MonitorConfig {
+    pub deduplicate: bool,
+    pub self_writes: SelfWritePolicy,
+    pub debounce: DebounceConfig,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            deduplicate: true,
+            self_writes: SelfWritePolicy::default(),
+            debounce: DebounceConfig::default(),
+        }
+    }
+}
+
+/// Content hashes shared by the write methods and the monitor.
+#[derive(Default)]
+pub(crate) struct ChangeTracker {
+    /// whether the monitor is running, writes are only fingerprinted then
+    pub active: bool,
+    /// fingerprint of the last change the monitor saw
+    last: Option<u64>,
+    /// fingerprint of the last write made through this plugin, until the monitor sees it
+    self_write: Option<u64>,
+}
+
+impl ChangeTracker {
+    pub fn reset(&mut self, active: bool) {
+        *self = Self {
+            active,
+            ..Self::default()
+        };
+    }
+
+    /// Remember the fingerprint of what a plugin write put on the clipboard.
+    pub fn record_self_write(&mut self, fingerprint: u64) {
+        if self.active {
+            self.self_write = Some(fingerprint);
+        }
+    }
+}
+
+/// Hash of everything on the clipboard, equal fingerprints mean the clipboard did not change.
+pub(crate) fn content_fingerprint(clipboard: &dyn ClipboardBackend) -> u64 {
+    ClipboardContents::read(clipboard, false).fingerprint()
+}
+
+/// Formats holding the image the way the platform stores it, in the order the backends read images from them.
+const NATIVE_IMAGE_FORMATS: &[&str] = &[
+    "image/png",
+    "public.png",
+    "public.tiff",
+    "PNG",
+    "CF_DIBV5",
+    "CF_DIB",
+];
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the image's bytes in its native format, so images are told apart without decoding them.
+/// Only when none of them can be read are the decoded pixels hashed, `decoded` when the caller already has them.
+fn image_hash(
+    clipboard: &dyn ClipboardBackend,
+    formats: &[String],
+    decoded: Option<&RustImageData>,
+) -> u64 {
+    let native = NATIVE_IMAGE_FORMATS
+        .iter()
+        .filter(|format| formats.iter().any(|available| available == *format))
+        .find_map(|format| clipboard.get_buffer(format).ok());
+    if let Some(bytes) = native {
+        return hash_bytes(&bytes);
+    }
+    let decoded = match decoded {
+        Some(image) => image.get_dynamic_image().ok(),
+        None => clipboard
+            .get_image()
+            .ok()
+            .and_then(|image| image.get_dynamic_image().ok()),
+    };
+    let mut hasher = DefaultHasher::new();
+    match decoded {
+        Some(image) => {
+            image.dimensions().hash(&mut hasher);
+            image.as_bytes().hash(&mut hasher);
+        }
+        None => true.hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Every raw format name, the formats the typed getters do not cover with a hash of their bytes,
+/// so changes that only touch custom formats, e.g. [`crate::Clipboard::write_buffer`], change the fingerprint.
+/// Images are hashed once by [`image_hash`], their other raw formats are only told apart by name.
+fn custom_formats(
+    clipboard: &dyn ClipboardBackend,
+    formats: &[String],
+) -> Vec<(String, Option<u64>)> {
+    formats
+        .iter()
+        .map(|format| {
+            let custom = !STANDARD_FORMATS.contains(&format.as_str())
+                && !normalized_mime(format).is_some_and(|mime| mime.starts_with("image/"));
+            let hash = custom
+                .then(|| clipboard.get_buffer(format).ok())
+                .flatten()
+                .map(|buffer| hash_bytes(&buffer));
+            (format.clone(), hash)
+        })
+        .collect()
+}
+
+/// Every available representation, read under a single backend lock.
+struct ClipboardContents {
+    formats: AvailableTypes,
+    sensitive: bool,
+    text: Option<String>,
+    html: Option<String>,
+    rtf: Option<String>,
+    files: Option<Vec<String>>,
+    /// the decoded image, only read when asked for
+    image: Option<RustImageData>,
+    /// see [`image_hash`]
+    image_hash: Option<u64>,
+    /// every raw format name, with a hash of the bytes of the custom ones
+    custom: Vec<(String, Option<u64>)>,
+}
+
+impl ClipboardContents {
+    /// Read every format, decoding the image only when `image` is set.
+    fn read(clipboard: &dyn ClipboardBackend, image: bool) -> Self {
+        let has = |format| clipboard.has(format).unwrap_or(false);
+        let formats = AvailableTypes {
+            text: has(ContentFormat::Text),
+            html: has(ContentFormat::Html),
+            rtf: has(ContentFormat::Rtf),
+            image: has(ContentFormat::Image),
+            files: has(ContentFormat::Files),
+        };
+        let raw_formats = clipboard.available_formats().unwrap_or_default();
+        let image = (formats.image && image)
+            .then(|| clipboard.get_image().ok())
+            .flatten();
+        Self {
+            text: formats.text.then(|| clipboard.get_text().ok()).flatten(),
+            html: formats.html.then(|| clipboard.get_html().ok()).flatten(),
+            rtf: formats
+                .rtf
+                .then(|| clipboard.get_rich_text().ok())
+                .flatten(),
+            files: formats.files.then(|| clipboard.get_files().ok()).flatten(),
+            image_hash: formats
+                .image
+                .then(|| image_hash(clipboard, &raw_formats, image.as_ref())),
+            image,
+            formats,
+            sensitive: is_sensitive(clipboard),
+            custom: custom_formats(clipboard, &raw_formats),
+        }
+    }
+
+    /// hash of every format's content, equal fingerprints mean the clipboard did not change
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.text.hash(&mut hasher);
+        self.html.hash(&mut hasher);
+        self.rtf.hash(&mut hasher);
+        self.files.hash(&mut hasher);
+        self.custom.hash(&mut hasher);
+        self.image_hash.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// A webview that started the monitor from js, with the scope and options of its `start_monitor` call and the
+/// channel its change events are sent over. Nothing is emitted to webviews, an emitted event would reach every
+/// listener of every webview.
+pub(crate) struct MonitorListener {
+    pub scope: ClipboardScope,
+    pub options: MonitorOptions,
+    pub channel: Channel<ClipboardChangeEvent>,
+    /// whether the webview may still get events, see [`check_events`]
+    pub may_read: Box<dyn Fn() -> bool + Send + Sync>,
+    /// `start_monitor` calls of the webview that were not stopped yet
+    pub starts: usize,
+}
+
+impl MonitorListener {
+    pub fn new<R: Runtime>(
+        webview: Webview<R>,
+        scope: ClipboardScope,
+        channel: Channel<ClipboardChangeEvent>,
+    ) -> Self {
+        Self {
+            scope,
+            options: MonitorOptions::default(),
+            channel,
+            may_read: Box::new(move || check_events(&webview).is_ok()),
+            starts: 0,
+        }
+    }
+}
+
+/// Who keeps the monitor of a selection running: the app from Rust and the webviews from js.
+#[derive(Default)]
+pub(crate) struct MonitorUsers {
+    /// options of [`crate::Clipboard::start_monitor_with_options`], `None` unless the monitor was started from Rust
+    pub app: Option<MonitorOptions>,
+    /// by webview label
+    pub webviews: HashMap<String, MonitorListener>,
+}
+
+impl MonitorUsers {
+    pub fn is_empty(&self) -> bool {
+        self.app.is_none() && self.webviews.is_empty()
+    }
+
+    /// The contents to read for every change, those enabled by any user.
+    fn options(&self) -> MonitorOptions {
+        self.webviews
+            .values()
+            .fold(self.app.clone().unwrap_or_default(), |options, listener| {
+                options.union(&listener.options)
+            })
+    }
+}
+
+pub struct ClipboardMonitor {
+    selection: ClipboardSelection,
+    clipboard: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    config: MonitorConfig,
+    tracker: Arc<Mutex<ChangeTracker>>,
+    seq: Arc<AtomicU64>,
+    subscribers: Subscribers,
+    users: Arc<Mutex<MonitorUsers>>,
+    #[cfg(feature = "history")]
+    history: Option<Arc<Mutex<crate::history::History>>>,
+}
+
+impl ClipboardMonitor {
+    pub fn new(clipboard: &Clipboard) -> Self {
+        Self {
+            selection: clipboard.selection,
+            clipboard: clipboard.clipboard.clone(),
+            config: clipboard.monitor_config.clone(),
+            tracker: clipboard.tracker.clone(),
+            seq: clipboard.seq.clone(),
+            subscribers: clipboard.subscribers.clone(),
+            users: clipboard.monitor_users.clone(),
+            #[cfg(feature = "history")]
+            history: clipboard.history.clone(),
+        }
+    }
+
+    /// whether events must carry every format, regardless of the options
+    #[cfg(feature = "history")]
+    fn records_history(&self) -> bool {
+        self.history.is_some()
+    }
+
+    #[cfg(not(feature = "history"))]
+    fn records_history(&self) -> bool {
+        false
+    }
+
+    /// Read the clipboard and build the change event with the contents in `options`, `None` when the change
+    /// should not be emitted.
+    fn snapshot(&self, options: &MonitorOptions) -> Result<Option<ClipboardChangeEvent>> {
+        let full = self.records_history();
+        let clipboard = self.clipboard.lock()?;
+        let contents = ClipboardContents::read(clipboard.as_ref(), full || options.image);
+        drop(clipboard);
+        let fingerprint = contents.fingerprint();
+        let origin = {
+            let mut tracker = self.tracker.lock()?;
+            if self.config.deduplicate && tracker.last == Some(fingerprint) {
+                return Ok(None);
+            }
+            tracker.last = Some(fingerprint);
+            if tracker.self_write == Some(fingerprint) {
+                tracker.self_write = None;
+                ChangeOrigin::Plugin
+            } else {
+                ChangeOrigin::External
+            }
+        };
+        if origin == ChangeOrigin::Plugin && self.config.self_writes == SelfWritePolicy::Suppress {
+            return Ok(None);
+        }
+        let mut event = ClipboardChangeEvent {
+            seq: self.seq.fetch_add(1, Ordering::SeqCst) + 1,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            origin,
+            selection: self.selection,
+            sensitive: contents.sensitive,
+            formats: contents.formats,
+            text: contents.text.filter(|_| full || options.text),
+            html: contents.html.filter(|_| full || options.html),
+            rtf: contents.rtf.filter(|_| full || options.rtf),
+            files: contents.files.filter(|_| full || options.files),
+            image: contents
+                .image
+                .filter(|_| full || options.image)
+                .and_then(|image| encode_png(&image).ok())
+                .map(|png| general_purpose::STANDARD.encode(png)),
+        };
+        #[cfg(feature = "history")]
+        if let Some(history) = &self.history {
+            // a failed write to disk must not hold back the event, the entry stays in memory until the next save
+            let _ = history.lock()?.record(&event);
+        }
+        if full {
+            event = options.apply(event);
+        }
+        Ok(Some(event))
+    }
+}
+
+impl ClipboardHandler for ClipboardMonitor {
+    fn on_clipboard_change(&mut self) {
+        let Ok(options) = self.users.lock().map(|users| users.options()) else {
+            return;
+        };
+        let Ok(Some(event)) = self.snapshot(&options) else {
+            return;
+        };
+        self.subscribers.dispatch(&event);
+        let Ok(users) = self.users.lock() else {
+            return;
+        };
+        // every webview gets its own copy over its channel, only while it may read and with what its scope allows
+        for listener in users.webviews.values() {
+            if (listener.may_read)() {
+                let event = listener.options.apply(event.clone());
+                let _ = listener.channel.send(listener.scope.restrict_event(event));
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{Error, Result};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tauri::{
+    ipc::{CommandArg, CommandItem, InvokeError},
+    utils::acl::ExecutionContext,
+    Manager, Runtime, Url, Webview,
+};
+
+/// Which windows may read the clipboard, set with [`crate::Builder::read_policy`]. Every window may read by default.
+///
+/// Applies to the read commands, the history commands that return entries and to starting the monitor.
+#[derive(Debug, Clone, Default)]
+pub struct ReadPolicy {
+    /// only the focused window may read
+    pub require_focus: bool,
+    /// hidden and minimized windows may not read
+    pub deny_background: bool,
+    /// calls from a remote page instead of the app's own frontend may not read, judged by the origin of the call
+    pub deny_remote: bool,
+    /// a webview may only read this long after it reported a user gesture, see `trackUserGestures` in the js api.
+    ///
+    /// This is an advisory heuristic, not a security boundary: gestures are reported by the frontend, so any script
+    /// running in the webview that is granted `report_user_gesture` can report them too. It keeps a well behaved
+    /// frontend from reading without the user, use `require_focus` and scopes against hostile scripts.
+    pub gesture_timeout: Option<Duration>,
+}
+
+/// The [`ReadPolicy`] together with the latest user gesture of each webview, managed by the plugin.
+pub(crate) struct AccessPolicy {
+    policy: ReadPolicy,
+    gestures: Mutex<HashMap<String, Instant>>,
+}
+
+impl AccessPolicy {
+    pub fn new(policy: ReadPolicy) -> Self {
+        Self {
+            policy,
+            gestures: Mutex::default(),
+        }
+    }
+
+    /// Note that the user just interacted with the webview `label`.
+    pub fn record_gesture(&self, label: &str) -> Result<()> {
+        if self.policy.gesture_timeout.is_some() {
+            self.gestures
+                .lock()?
+                .insert(label.to_string(), Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Fails unless `webview` may read the clipboard, for a caller from a `remote` origin or not.
+    /// Window state that cannot be queried counts as denied.
+    pub fn check_read<R: Runtime>(&self, webview: &Webview<R>, remote: bool) -> Result<()> {
+        let denied = |reason| {
+            Err(Error::AccessDenied {
+                window: webview.label().to_string(),
+                reason,
+            })
+        };
+        let window = webview.window();
+        if self.policy.require_focus && !window.is_focused().unwrap_or(false) {
+            return denied("it is not focused");
+        }
+        if self.policy.deny_background
+            && (!window.is_visible().unwrap_or(false) || window.is_minimized().unwrap_or(true))
+        {
+            return denied("it is hidden or minimized");
+        }
+        if self.policy.deny_remote && remote {
+            return denied("the call comes from a remote page");
+        }
+        if let Some(timeout) = self.policy.gesture_timeout {
+            let recent = self
+                .gestures
+                .lock()?
+                .get(webview.label())
+                .is_some_and(|gesture| gesture.elapsed() <= timeout);
+            if !recent {
+                return denied("there was no recent user gesture");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `url` belongs to the app's own frontend: a custom protocol, `http(s)://<protocol>.localhost` on Windows
+/// and Android, or the dev server while developing.
+fn is_local<R: Runtime>(webview: &Webview<R>, url: &Url) -> bool {
+    match url.scheme() {
+        "http" | "https" => {}
+        "file" | "data" | "blob" | "about" => return false,
+        _ => return true,
+    }
+    if url
+        .host_str()
+        .is_some_and(|host| host.ends_with(".localhost"))
+    {
+        return true;
+    }
+    tauri::is_dev()
+        && webview
+            .config()
+            .build
+            .dev_url
+            .as_ref()
+            .is_some_and(|dev_url| dev_url.origin() == url.origin())
+}
+
+/// The webview that invoked a command, and whether the call came from a remote origin.
+///
+/// The origin is that of the call, not the url of the webview, e.g. a remote iframe inside the app's own page is
+/// remote. Tauri resolves it when it picks the capabilities that allow the command, a capability for remote urls
+/// only matches calls from those urls.
+pub(crate) struct Invoker<R: Runtime> {
+    pub webview: Webview<R>,
+    remote: bool,
+}
+
+impl<'a, R: Runtime> CommandArg<'a, R> for Invoker<R> {
+    fn from_command(command: CommandItem<'a, R>) -> std::result::Result<Self, InvokeError> {
+        let webview = command.message.webview();
+        let remote = match command.acl {
+            Some(acl) => acl
+                .iter()
+                .any(|command| matches!(command.context, ExecutionContext::Remote { .. })),
+            // commands that are not checked against capabilities, judge by the page instead
+            None => !webview
+                .url()
+                .map(|url| is_local(&webview, &url))
+                .unwrap_or(false),
+        };
+        Ok(Self { webview, remote })
+    }
+}
+
+/// [`AccessPolicy::check_read`] with the policy managed by the plugin, every call may read when there is none.
+pub(crate) fn check_read<R: Runtime>(invoker: &Invoker<R>) -> Result<()> {
+    match invoker.webview.try_state::<AccessPolicy>() {
+        Some(policy) => policy.check_read(&invoker.webview, invoker.remote),
+        None => Ok(()),
+    }
+}
+
+/// Whether change events of a monitor may still go to `webview`, judged by the page it shows at the moment.
+pub(crate) fn check_events<R: Runtime>(webview: &Webview<R>) -> Result<()> {
+    match webview.try_state::<AccessPolicy>() {
+        Some(policy) => {
+            let remote = !webview
+                .url()
+                .map(|url| is_local(webview, &url))
+                .unwrap_or(false);
+            policy.check_read(webview, remote)
+        }
+        None => Ok(()),
+    }
+}
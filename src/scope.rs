@@ -0,0 +1,664 @@
+use crate::{
+    backend::normalized_mime, desktop::AvailableTypes, ClipboardChangeEvent, ClipboardSnapshot,
+    Error, Result,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tauri::{
+    ipc::{CommandArg, CommandItem, CommandScope, GlobalScope, InvokeError},
+    scope::fs::Scope as FsScope,
+    utils::config::FsScope as FsScopeConfig,
+    Manager, Runtime, Url,
+};
+
+/// One entry of the `allow` or `deny` list of a clipboard permission, or of the plugin's global scope.
+///
+/// Allow entries restrict a command once any of them lists formats or paths, deny entries always win.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopeEntry {
+    /// glob of file paths that `read_files` may return and `write_files` may write, e.g. `$HOME/Documents/**`
+    pub path: Option<String>,
+    /// `text`, `html`, `rtf`, `image`, `files`, a platform format name of raw buffers, or `*` for all of them
+    pub format: Option<String>,
+    /// only apply the entry to reads or to writes, it applies to both when missing
+    pub access: Option<ScopeAccess>,
+    /// largest payload in bytes that may be read or written, only used by allow entries
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScopeAccess {
+    Read,
+    Write,
+}
+
+impl ScopeAccess {
+    pub(crate) fn verb(self) -> &'static str {
+        match self {
+            ScopeAccess::Read => "reading",
+            ScopeAccess::Write => "writing",
+        }
+    }
+}
+
+impl ScopeEntry {
+    fn applies_to(&self, access: ScopeAccess) -> bool {
+        self.access.is_none() || self.access == Some(access)
+    }
+
+    /// An entry listing paths is about files, even without a format, e.g. its size limit only applies to them.
+    fn allowed_format(&self) -> Option<&str> {
+        self.format
+            .as_deref()
+            .or_else(|| self.path.as_ref().map(|_| "files"))
+    }
+
+    fn matches(format: Option<&str>, name: &str) -> bool {
+        format == Some("*") || format == Some(name)
+    }
+}
+
+/// The typed format whose contents a raw format name carries, e.g. `text` for `UTF8_STRING` or `files` for
+/// `text/uri-list`, so raw buffers cannot get around the scope of the typed commands.
+fn typed_format(format: &str) -> Option<&'static str> {
+    let mime = normalized_mime(format)?;
+    Some(match mime.as_str() {
+        "text/plain" => "text",
+        "text/html" => "html",
+        "text/rtf" => "rtf",
+        "text/uri-list" => "files",
+        mime if mime.starts_with("image/") => "image",
+        _ => return None,
+    })
+}
+
+/// The uris of a raw file list, `None` for binary lists like `CF_HDROP` that cannot be parsed here.
+fn buffer_uris(format: &str, buffer: &[u8]) -> Option<Vec<String>> {
+    match format {
+        "text/uri-list"
+        | "x-special/gnome-copied-files"
+        | "x-special/nautilus-clipboard"
+        | "public.file-url"
+        | "public.url" => {}
+        _ => return None,
+    }
+    let text = String::from_utf8_lossy(buffer);
+    let mut lines = text.lines().map(str::trim).peekable();
+    // the gnome and nautilus lists start with the operation
+    if lines
+        .peek()
+        .is_some_and(|line| *line == "copy" || *line == "cut" || line.starts_with("x-special/"))
+    {
+        lines.next();
+    }
+    Some(
+        lines
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Allowed and denied file paths of one access.
+struct PathScope {
+    scope: FsScope,
+    /// without allow entries every path is allowed unless it is denied
+    allow_listed: bool,
+}
+
+/// Allowed and denied path patterns, the key of a cached [`PathScope`].
+type PathPatterns = (Vec<PathBuf>, Vec<PathBuf>);
+
+/// The path scopes built so far, managed by the plugin. Building one resolves and compiles every pattern,
+/// so each is only built by the first command with its patterns and then shared, e.g. with the monitor.
+#[derive(Default)]
+pub(crate) struct PathScopes(Mutex<HashMap<PathPatterns, Arc<PathScope>>>);
+
+impl PathScope {
+    fn new<R: Runtime>(
+        webview: &tauri::Webview<R>,
+        allow: &[Arc<ScopeEntry>],
+        deny: &[Arc<ScopeEntry>],
+        access: ScopeAccess,
+    ) -> std::result::Result<Option<Arc<Self>>, InvokeError> {
+        let paths = |entries: &[Arc<ScopeEntry>]| -> Vec<PathBuf> {
+            entries
+                .iter()
+                .filter(|entry| entry.applies_to(access))
+                .filter_map(|entry| entry.path.as_ref().map(PathBuf::from))
+                .collect()
+        };
+        let patterns = (paths(allow), paths(deny));
+        if patterns.0.is_empty() && patterns.1.is_empty() {
+            return Ok(None);
+        }
+        let Some(cache) = webview.try_state::<PathScopes>() else {
+            return Ok(Some(Arc::new(Self::build(webview, patterns)?)));
+        };
+        let mut cache = cache.0.lock().map_err(Error::from)?;
+        if let Some(scope) = cache.get(&patterns) {
+            return Ok(Some(scope.clone()));
+        }
+        let scope = Arc::new(Self::build(webview, patterns.clone())?);
+        cache.insert(patterns, scope.clone());
+        Ok(Some(scope))
+    }
+
+    fn build<R: Runtime>(
+        webview: &tauri::Webview<R>,
+        (allow, deny): PathPatterns,
+    ) -> tauri::Result<Self> {
+        Ok(Self {
+            allow_listed: !allow.is_empty(),
+            scope: FsScope::new(
+                webview,
+                &FsScopeConfig::Scope {
+                    allow,
+                    deny,
+                    require_literal_leading_dot: None,
+                },
+            )?,
+        })
+    }
+
+    /// Paths that do not exist are never allowed, they cannot be resolved against the patterns. File uris are
+    /// decoded, e.g. `file://localhost/tmp/a%20b` is `/tmp/a b`, and uris that are not local paths are not allowed.
+    fn allows(&self, path: &str) -> bool {
+        let path = if path.starts_with("file:") {
+            match Url::parse(path)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+            {
+                Some(path) => path,
+                None => return false,
+            }
+        } else {
+            PathBuf::from(path)
+        };
+        if self.allow_listed {
+            self.scope.is_allowed(path)
+        } else {
+            !self.scope.is_forbidden(path)
+        }
+    }
+}
+
+/// Scope of a clipboard command: the entries of the permissions that granted it, together with the global scope.
+pub(crate) struct ClipboardScope {
+    allow: Vec<Arc<ScopeEntry>>,
+    deny: Vec<Arc<ScopeEntry>>,
+    read_paths: Option<Arc<PathScope>>,
+    write_paths: Option<Arc<PathScope>>,
+}
+
+impl<'a, R: Runtime> CommandArg<'a, R> for ClipboardScope {
+    fn from_command(command: CommandItem<'a, R>) -> std::result::Result<Self, InvokeError> {
+        let webview = command.message.webview();
+        let global = GlobalScope::<ScopeEntry>::from_command(CommandItem {
+            plugin: command.plugin,
+            name: command.name,
+            key: command.key,
+            message: command.message,
+            acl: command.acl,
+        })?;
+        let scope = CommandScope::<ScopeEntry>::from_command(command)?;
+        let allow: Vec<_> = scope
+            .allows()
+            .iter()
+            .chain(global.allows())
+            .cloned()
+            .collect();
+        let deny: Vec<_> = scope
+            .denies()
+            .iter()
+            .chain(global.denies())
+            .cloned()
+            .collect();
+        Ok(Self {
+            read_paths: PathScope::new(&webview, &allow, &deny, ScopeAccess::Read)?,
+            write_paths: PathScope::new(&webview, &allow, &deny, ScopeAccess::Write)?,
+            allow,
+            deny,
+        })
+    }
+}
+
+impl ClipboardScope {
+    fn entries(
+        entries: &[Arc<ScopeEntry>],
+        access: ScopeAccess,
+    ) -> impl Iterator<Item = &ScopeEntry> {
+        entries
+            .iter()
+            .map(Arc::as_ref)
+            .filter(move |entry| entry.applies_to(access))
+    }
+
+    fn allows_format(&self, format: &str, access: ScopeAccess) -> bool {
+        // a deny entry that only lists paths denies those paths, not every file
+        let denied = Self::entries(&self.deny, access).any(|entry| {
+            entry.path.is_none()
+                && (entry.format.is_none() || ScopeEntry::matches(entry.format.as_deref(), format))
+        });
+        if denied {
+            return false;
+        }
+        // allow entries that only list paths restrict those, not which formats are allowed
+        let mut allowed = Self::entries(&self.allow, access)
+            .filter_map(|entry| entry.format.as_deref())
+            .peekable();
+        allowed.peek().is_none()
+            || allowed.any(|allowed| ScopeEntry::matches(Some(allowed), format))
+    }
+
+    fn max_size(&self, format: &str, access: ScopeAccess) -> Option<u64> {
+        Self::entries(&self.allow, access)
+            .filter(|entry| {
+                let allowed = entry.allowed_format();
+                allowed.is_none() || ScopeEntry::matches(allowed, format)
+            })
+            .filter_map(|entry| entry.max_size)
+            .min()
+    }
+
+    /// Fails unless `format` may be read or written.
+    pub fn check_format(&self, format: &str, access: ScopeAccess) -> Result<()> {
+        if self.allows_format(format, access) {
+            Ok(())
+        } else {
+            Err(Error::ScopeDenied(format!("{} {format}", access.verb())))
+        }
+    }
+
+    /// Fails unless a payload of `size` bytes of `format` may be read or written.
+    pub fn check(&self, format: &str, size: usize, access: ScopeAccess) -> Result<()> {
+        self.check_format(format, access)?;
+        match self.max_size(format, access) {
+            Some(max) if size as u64 > max => Err(Error::PayloadTooLarge {
+                format: format.to_string(),
+                size: size as u64,
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails unless the raw `format` and the typed format it carries may be read or written.
+    pub fn check_buffer_format(&self, format: &str, access: ScopeAccess) -> Result<()> {
+        self.check_format(format, access)?;
+        match typed_format(format) {
+            Some(typed) => self.check_format(typed, access),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless the raw `buffer` of `format` may be read or written, including every file of a file list.
+    pub fn check_buffer(&self, format: &str, buffer: &[u8], access: ScopeAccess) -> Result<()> {
+        self.check(format, buffer.len(), access)?;
+        match typed_format(format) {
+            Some("files") => {
+                let paths = match access {
+                    ScopeAccess::Read => &self.read_paths,
+                    ScopeAccess::Write => &self.write_paths,
+                };
+                let files = match buffer_uris(format, buffer) {
+                    Some(files) => files,
+                    None if paths.is_none() => Vec::new(),
+                    None => {
+                        return Err(Error::ScopeDenied(format!(
+                            "{} {format}, its files cannot be checked",
+                            access.verb()
+                        )))
+                    }
+                };
+                match access {
+                    ScopeAccess::Read => {
+                        if self.read_files(files.clone())?.len() != files.len() {
+                            return Err(Error::ScopeDenied(format!(
+                                "reading {format}, it lists files outside the scope"
+                            )));
+                        }
+                    }
+                    ScopeAccess::Write => self.check_write_files(&files)?,
+                }
+                self.check("files", buffer.len(), access)
+            }
+            Some(typed) => self.check(typed, buffer.len(), access),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the file path or uri `path` may be read or written, without checking the `files` format.
+    pub fn allows_path(&self, path: &str, access: ScopeAccess) -> bool {
+        let paths = match access {
+            ScopeAccess::Read => &self.read_paths,
+            ScopeAccess::Write => &self.write_paths,
+        };
+        match paths {
+            Some(paths) => paths.allows(path),
+            None => true,
+        }
+    }
+
+    /// Fails unless `content` may be written by `write_contents`.
+    pub fn check_content(&self, content: &crate::ClipboardItemContent) -> Result<()> {
+        use crate::ClipboardItemContent;
+        match content {
+            ClipboardItemContent::Text(text) => self.check("text", text.len(), ScopeAccess::Write),
+            ClipboardItemContent::Html(html) => self.check("html", html.len(), ScopeAccess::Write),
+            ClipboardItemContent::Rtf(rtf) => self.check("rtf", rtf.len(), ScopeAccess::Write),
+            ClipboardItemContent::Image(image) => {
+                let bytes = crate::encoding::decode_base64_image(image)?;
+                self.check("image", bytes.len(), ScopeAccess::Write)
+            }
+            ClipboardItemContent::Files(files) => self.check_write_files(files),
+            ClipboardItemContent::Custom { format, data } => {
+                self.check_buffer(format, data, ScopeAccess::Write)
+            }
+        }
+    }
+
+    /// Fails unless all `files` may be written.
+    pub fn check_write_files(&self, files: &[String]) -> Result<()> {
+        self.check(
+            "files",
+            files.iter().map(String::len).sum(),
+            ScopeAccess::Write,
+        )?;
+        match files
+            .iter()
+            .find(|file| !self.allows_path(file, ScopeAccess::Write))
+        {
+            Some(file) => Err(Error::ScopeDenied(format!("writing {file}"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless every format of a saved snapshot may be written. The image counts with its decoded pixels,
+    /// the snapshot does not keep the encoded bytes it was read from.
+    pub fn check_saved(&self, saved: &crate::SavedClipboard) -> Result<()> {
+        let typed = [
+            ("text", &saved.text),
+            ("html", &saved.html),
+            ("rtf", &saved.rtf),
+        ];
+        for (format, content) in typed {
+            if let Some(content) = content {
+                self.check(format, content.len(), ScopeAccess::Write)?;
+            }
+        }
+        if let Some(files) = &saved.files {
+            self.check_write_files(files)?;
+        }
+        if let Some(image) = &saved.image {
+            self.check("image", image.as_bytes().len(), ScopeAccess::Write)?;
+        }
+        for (format, buffer) in &saved.other {
+            self.check_buffer(format, buffer, ScopeAccess::Write)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the files that may not be read, then check what is left.
+    pub fn read_files(&self, files: Vec<String>) -> Result<Vec<String>> {
+        self.check_format("files", ScopeAccess::Read)?;
+        let files: Vec<_> = files
+            .into_iter()
+            .filter(|file| self.allows_path(file, ScopeAccess::Read))
+            .collect();
+        self.check(
+            "files",
+            files.iter().map(String::len).sum(),
+            ScopeAccess::Read,
+        )?;
+        Ok(files)
+    }
+
+    /// Remove the contents that may not be read, `true` when any readable contents are left.
+    fn restrict_contents(
+        &self,
+        text: &mut Option<String>,
+        html: &mut Option<String>,
+        rtf: &mut Option<String>,
+        image: &mut Option<String>,
+        files: &mut Option<Vec<String>>,
+    ) -> bool {
+        let allowed = |format: &str, content: &Option<String>| {
+            content.as_ref().is_some_and(|content| {
+                // images are carried base64 encoded, their size is that of the decoded bytes
+                let size = match format {
+                    "image" => match crate::encoding::decode_base64_image(content) {
+                        Ok(bytes) => bytes.len(),
+                        Err(_) => return false,
+                    },
+                    _ => content.len(),
+                };
+                self.check(format, size, ScopeAccess::Read).is_ok()
+            })
+        };
+        if !allowed("text", text) {
+            *text = None;
+        }
+        if !allowed("html", html) {
+            *html = None;
+        }
+        if !allowed("rtf", rtf) {
+            *rtf = None;
+        }
+        if !allowed("image", image) {
+            *image = None;
+        }
+        *files = files.take().and_then(|files| self.read_files(files).ok());
+        text.is_some() || html.is_some() || rtf.is_some() || image.is_some() || files.is_some()
+    }
+
+    /// Leave out the formats that may not be read, the caller cannot tell whether the clipboard holds them.
+    pub fn restrict_types(&self, types: AvailableTypes) -> AvailableTypes {
+        let readable = |format| self.check_format(format, ScopeAccess::Read).is_ok();
+        AvailableTypes {
+            text: types.text && readable("text"),
+            html: types.html && readable("html"),
+            rtf: types.rtf && readable("rtf"),
+            image: types.image && readable("image"),
+            files: types.files && readable("files"),
+        }
+    }
+
+    /// Remove the contents of `snapshot` that may not be read.
+    pub fn restrict_snapshot(&self, mut snapshot: ClipboardSnapshot) -> ClipboardSnapshot {
+        snapshot.formats = self.restrict_types(snapshot.formats);
+        self.restrict_contents(
+            &mut snapshot.text,
+            &mut snapshot.html,
+            &mut snapshot.rtf,
+            &mut snapshot.image,
+            &mut snapshot.files,
+        );
+        snapshot
+    }
+
+    /// Remove the contents of a monitor event that may not be read.
+    pub fn restrict_event(&self, mut event: ClipboardChangeEvent) -> ClipboardChangeEvent {
+        event.formats = self.restrict_types(event.formats);
+        self.restrict_contents(
+            &mut event.text,
+            &mut event.html,
+            &mut event.rtf,
+            &mut event.image,
+            &mut event.files,
+        );
+        event
+    }
+
+    /// Remove the contents of a history entry that may not be read, `None` when nothing readable is left.
+    /// Listed entries do not carry their image, it only counts as readable when its format may be read,
+    /// the size is checked once [`crate::Clipboard::history_get`] loads it.
+    #[cfg(feature = "history")]
+    pub fn restrict_entry(&self, mut entry: crate::HistoryEntry) -> Option<crate::HistoryEntry> {
+        let unloaded_image = entry.image.is_none()
+            && entry.formats.image
+            && self.check_format("image", ScopeAccess::Read).is_ok();
+        let readable = self.restrict_contents(
+            &mut entry.text,
+            &mut entry.html,
+            &mut entry.rtf,
+            &mut entry.image,
+            &mut entry.files,
+        );
+        (readable || unloaded_image).then_some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(format: &str, access: Option<ScopeAccess>, max_size: Option<u64>) -> ScopeEntry {
+        ScopeEntry {
+            path: None,
+            format: Some(format.to_string()),
+            access,
+            max_size,
+        }
+    }
+
+    /// A scope without path entries, those need a webview to resolve.
+    fn scope(allow: Vec<ScopeEntry>, deny: Vec<ScopeEntry>) -> ClipboardScope {
+        ClipboardScope {
+            allow: allow.into_iter().map(Arc::new).collect(),
+            deny: deny.into_iter().map(Arc::new).collect(),
+            read_paths: None,
+            write_paths: None,
+        }
+    }
+
+    #[test]
+    fn empty_scope_allows_everything() {
+        let scope = scope(vec![], vec![]);
+        scope.check("image", usize::MAX, ScopeAccess::Read).unwrap();
+        scope
+            .check_buffer("application/x-custom", &[1, 2], ScopeAccess::Write)
+            .unwrap();
+    }
+
+    #[test]
+    fn allow_entries_restrict_formats() {
+        let scope = scope(
+            vec![
+                entry("text", None, None),
+                entry("html", Some(ScopeAccess::Read), None),
+            ],
+            vec![],
+        );
+        scope.check_format("text", ScopeAccess::Write).unwrap();
+        scope.check_format("html", ScopeAccess::Read).unwrap();
+        assert!(matches!(
+            scope.check_format("html", ScopeAccess::Write),
+            Err(Error::ScopeDenied(_))
+        ));
+        assert!(scope.check_format("image", ScopeAccess::Read).is_err());
+        assert!(self::scope(vec![entry("*", None, None)], vec![])
+            .check_format("image", ScopeAccess::Read)
+            .is_ok());
+    }
+
+    #[test]
+    fn deny_entries_win() {
+        let scope = scope(
+            vec![entry("*", None, None)],
+            vec![entry("image", Some(ScopeAccess::Read), None)],
+        );
+        assert!(scope.check_format("image", ScopeAccess::Read).is_err());
+        scope.check_format("image", ScopeAccess::Write).unwrap();
+        // a deny entry that only lists paths leaves other files alone
+        let paths_only = ScopeEntry {
+            path: Some("$HOME/.ssh/**".to_string()),
+            format: None,
+            access: None,
+            max_size: None,
+        };
+        self::scope(vec![], vec![paths_only])
+            .check_format("files", ScopeAccess::Read)
+            .unwrap();
+    }
+
+    #[test]
+    fn smallest_max_size_applies() {
+        let scope = scope(
+            vec![entry("*", None, Some(100)), entry("image", None, Some(10))],
+            vec![],
+        );
+        scope.check("text", 100, ScopeAccess::Read).unwrap();
+        scope.check("image", 10, ScopeAccess::Read).unwrap();
+        match scope.check("image", 11, ScopeAccess::Read) {
+            Err(Error::PayloadTooLarge { format, size, max }) => {
+                assert_eq!((format.as_str(), size, max), ("image", 11, 10))
+            }
+            other => panic!("{other:?}"),
+        }
+        assert!(scope.check("text", 101, ScopeAccess::Write).is_err());
+    }
+
+    #[test]
+    fn raw_buffers_follow_their_typed_format() {
+        let scope = scope(vec![], vec![entry("html", None, None)]);
+        assert!(scope
+            .check_buffer_format("text/html", ScopeAccess::Read)
+            .is_err());
+        assert!(scope
+            .check_buffer("text/html;charset=utf-8", b"<b>", ScopeAccess::Write)
+            .is_err());
+        scope
+            .check_buffer_format("UTF8_STRING", ScopeAccess::Read)
+            .unwrap();
+        assert_eq!(typed_format("PNG"), Some("image"));
+        assert_eq!(typed_format("application/x-custom"), None);
+        assert_eq!(typed_format("image/png"), Some("image"));
+        assert_eq!(typed_format("x-special/gnome-copied-files"), Some("files"));
+    }
+
+    #[test]
+    fn file_lists_are_parsed() {
+        assert_eq!(
+            buffer_uris(
+                "text/uri-list",
+                b"# comment\r\nfile:///a\r\n\r\nfile:///b\r\n"
+            )
+            .unwrap(),
+            ["file:///a", "file:///b"]
+        );
+        assert_eq!(
+            buffer_uris("x-special/gnome-copied-files", b"cut\nfile:///a").unwrap(),
+            ["file:///a"]
+        );
+        assert_eq!(buffer_uris("CF_HDROP", &[0; 20]), None);
+    }
+
+    #[test]
+    fn restricted_contents_are_removed() {
+        let scope = scope(vec![entry("text", None, Some(3))], vec![]);
+        let (mut text, mut html) = (Some("abc".to_string()), Some("<b>".to_string()));
+        let (mut rtf, mut image, mut files) = (None, None, Some(vec!["file:///a".to_string()]));
+        assert!(scope.restrict_contents(&mut text, &mut html, &mut rtf, &mut image, &mut files));
+        assert_eq!((text.as_deref(), html, files), (Some("abc"), None, None));
+        let mut long = Some("abcd".to_string());
+        assert!(!scope.restrict_contents(&mut long, &mut None, &mut None, &mut None, &mut None));
+    }
+
+    #[test]
+    fn image_size_is_the_decoded_size() {
+        let scope = scope(vec![entry("image", None, Some(3))], vec![]);
+        // 4 base64 characters carry 3 bytes
+        let mut image = Some("AQID".to_string());
+        assert!(scope.restrict_contents(&mut None, &mut None, &mut None, &mut image, &mut None));
+        let mut image = Some("AQIDBA==".to_string());
+        assert!(!scope.restrict_contents(&mut None, &mut None, &mut None, &mut image, &mut None));
+        assert_eq!(image, None);
+    }
+}
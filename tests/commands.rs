@@ -0,0 +1,620 @@
+//! Runs the commands through tauri's IPC on the mock runtime, with the clipboard kept in memory.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tauri::{
+    ipc::{CallbackFn, InvokeBody, InvokeResponseBody},
+    test::{get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY},
+    utils::acl::{
+        resolved::{Resolved, ResolvedCommand, ResolvedScope},
+        ExecutionContext,
+    },
+    webview::InvokeRequest,
+    App, Listener, Manager, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_plugin_clipboard::{Builder, Clipboard, Error, MemoryBackend, ReadPolicy};
+
+const COMMANDS: &[&str] = &[
+    "read_text",
+    "write_text",
+    "read_html",
+    "write_html",
+    "read_all",
+    "read_buffer",
+    "write_buffer",
+    "read_image",
+    "write_image_binary",
+    "save_snapshot",
+    "restore_snapshot",
+    "read_files",
+    "read_files_uris",
+    "write_files_uris",
+    "discard_snapshot",
+    "available_formats",
+    "available_types",
+    "has_html",
+    "report_user_gesture",
+    "clear",
+    "start_monitor",
+    "stop_monitor",
+    "is_monitor_running",
+    #[cfg(feature = "history")]
+    "history_list",
+    #[cfg(feature = "history")]
+    "history_copy",
+    #[cfg(feature = "history")]
+    "history_pin",
+    #[cfg(feature = "history")]
+    "history_delete",
+];
+
+const REMOTE_URL: &str = "https://example.com/page";
+
+/// What was sent over channels, with the label of the webview each went to.
+type Sent = Arc<Mutex<Vec<(String, Value)>>>;
+
+struct TestApp {
+    app: App<MockRuntime>,
+    webview: WebviewWindow<MockRuntime>,
+    sent: Sent,
+}
+
+/// An app with the plugin built by `builder` on a [`MemoryBackend`], where every command in [`COMMANDS`] is allowed
+/// for calls from `context` and `scope` is the plugin's global scope.
+fn app_with(builder: Builder, context: ExecutionContext, scope: ResolvedScope) -> TestApp {
+    let mut resolved = Resolved::default();
+    for command in COMMANDS {
+        resolved.allowed_commands.insert(
+            format!("plugin:clipboard|{command}"),
+            vec![ResolvedCommand {
+                context: context.clone(),
+                windows: vec!["*".parse().unwrap()],
+                ..Default::default()
+            }],
+        );
+    }
+    resolved.global_scope.insert("clipboard".to_string(), scope);
+    let mut tauri_context = mock_context(noop_assets());
+    *tauri_context.runtime_authority_mut() =
+        tauri::runtime_authority!(Default::default(), resolved);
+    let sent = Sent::default();
+    let record = sent.clone();
+    let app = mock_builder()
+        .plugin(builder.backend(MemoryBackend::new()).build())
+        .channel_interceptor(move |webview, _, _, body| {
+            if let InvokeResponseBody::Json(json) = body {
+                let message = serde_json::from_str(json).unwrap();
+                record
+                    .lock()
+                    .unwrap()
+                    .push((webview.label().to_string(), message));
+            }
+            true
+        })
+        .build(tauri_context)
+        .unwrap();
+    let webview = WebviewWindowBuilder::new(&app, "main", Default::default())
+        .build()
+        .unwrap();
+    TestApp { app, webview, sent }
+}
+
+fn app() -> TestApp {
+    app_with(
+        Builder::new(),
+        ExecutionContext::Local,
+        ResolvedScope::default(),
+    )
+}
+
+fn scoped(allow: Vec<Value>, deny: Vec<Value>) -> TestApp {
+    let scope = ResolvedScope {
+        allow: allow.into_iter().map(Into::into).collect(),
+        deny: deny.into_iter().map(Into::into).collect(),
+    };
+    app_with(Builder::new(), ExecutionContext::Local, scope)
+}
+
+impl TestApp {
+    fn clipboard(&self) -> tauri::State<'_, Clipboard> {
+        self.app.state::<Clipboard>()
+    }
+
+    /// Send the following calls from the window `label`, opened if it does not exist yet.
+    fn use_window(&mut self, label: &str) {
+        self.webview = match self.app.get_webview_window(label) {
+            Some(webview) => webview,
+            None => WebviewWindowBuilder::new(&self.app, label, Default::default())
+                .build()
+                .unwrap(),
+        };
+    }
+
+    /// The messages sent over channels to the webview `label` within `wait`.
+    fn sent_to(&self, label: &str, wait: Duration) -> Vec<Value> {
+        std::thread::sleep(wait);
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(to, _)| to == label)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    fn request(
+        &self,
+        command: &str,
+        url: &str,
+        body: InvokeBody,
+        headers: tauri::http::HeaderMap,
+    ) -> Result<InvokeResponseBody, Value> {
+        get_ipc_response(
+            &self.webview,
+            InvokeRequest {
+                cmd: format!("plugin:clipboard|{command}"),
+                callback: CallbackFn(0),
+                error: CallbackFn(1),
+                url: url.parse().unwrap(),
+                body,
+                headers,
+                invoke_key: INVOKE_KEY.to_string(),
+            },
+        )
+    }
+
+    fn invoke(&self, command: &str, args: Value) -> Result<InvokeResponseBody, Value> {
+        let url = if cfg!(any(windows, target_os = "android")) {
+            "http://tauri.localhost"
+        } else {
+            "tauri://localhost"
+        };
+        self.request(command, url, InvokeBody::Json(args), Default::default())
+    }
+
+    fn call<T: serde::de::DeserializeOwned>(&self, command: &str, args: Value) -> T {
+        self.invoke(command, args)
+            .unwrap_or_else(|err| panic!("{command} failed: {err}"))
+            .deserialize()
+            .unwrap()
+    }
+
+    fn bytes(&self, command: &str, args: Value) -> Vec<u8> {
+        match self.invoke(command, args) {
+            Ok(InvokeResponseBody::Raw(bytes)) => bytes,
+            other => panic!("{command} did not answer with bytes: {other:?}"),
+        }
+    }
+
+    /// The `kind` of the error `command` failed with.
+    fn error(&self, command: &str, args: Value) -> String {
+        match self.invoke(command, args) {
+            Err(err) => err["kind"].as_str().unwrap_or_default().to_string(),
+            Ok(_) => panic!("{command} succeeded"),
+        }
+    }
+}
+
+#[test]
+fn text_and_html_round_trip() {
+    let app = app();
+    app.call::<()>("write_text", json!({ "text": "hello" }));
+    assert_eq!(app.call::<String>("read_text", json!({})), "hello");
+    assert_eq!(app.error("read_html", json!({})), "formatUnavailable");
+    app.call::<()>("write_html", json!({ "html": "<b>hi</b>" }));
+    assert_eq!(app.call::<String>("read_html", json!({})), "<b>hi</b>");
+    // the plugin's own state sees what the commands wrote
+    assert_eq!(app.clipboard().read_html().unwrap(), "<b>hi</b>");
+    app.call::<()>("clear", json!({}));
+    assert_eq!(app.error("read_text", json!({})), "formatUnavailable");
+}
+
+#[test]
+fn read_all_returns_every_format() {
+    let app = app();
+    app.clipboard().write_text("plain".to_string()).unwrap();
+    let snapshot: Value = app.call("read_all", json!({}));
+    assert_eq!(snapshot["text"], "plain");
+    assert_eq!(snapshot["formats"]["text"], true);
+    assert_eq!(snapshot["formats"]["html"], false);
+}
+
+#[test]
+fn restore_after_returns_the_error_of_the_call() {
+    let app = app();
+    let clipboard = app.clipboard();
+    clipboard.write_text("before".to_string()).unwrap();
+    let result = clipboard.restore_after(|clipboard| {
+        clipboard.write_text("during".to_string())?;
+        Err::<(), _>(Error::SnapshotNotFound(7))
+    });
+    assert!(matches!(result, Err(Error::SnapshotNotFound(7))));
+    assert_eq!(clipboard.read_text().unwrap(), "before");
+}
+
+#[test]
+fn buffers_travel_as_raw_bytes() {
+    let app = app();
+    let mut headers = tauri::http::HeaderMap::new();
+    headers.insert("format", "application/x-shape".parse().unwrap());
+    let url = if cfg!(any(windows, target_os = "android")) {
+        "http://tauri.localhost"
+    } else {
+        "tauri://localhost"
+    };
+    app.request("write_buffer", url, InvokeBody::Raw(vec![1, 2, 3]), headers)
+        .unwrap();
+    assert_eq!(
+        app.bytes("read_buffer", json!({ "format": "application/x-shape" })),
+        [1, 2, 3]
+    );
+}
+
+#[test]
+fn snapshots_bring_back_every_format() {
+    let app = app();
+    app.clipboard()
+        .write_html_and_text("<b>x</b>".to_string(), "x".to_string())
+        .unwrap();
+    let id: u64 = app.call("save_snapshot", json!({}));
+    app.call::<()>("write_text", json!({ "text": "borrowed" }));
+    app.call::<()>("restore_snapshot", json!({ "id": id }));
+    assert_eq!(app.call::<String>("read_text", json!({})), "x");
+    assert_eq!(app.call::<String>("read_html", json!({})), "<b>x</b>");
+    // a handle is only good for one restore
+    assert_eq!(
+        app.error("restore_snapshot", json!({ "id": id })),
+        "snapshotNotFound"
+    );
+}
+
+#[test]
+fn snapshots_belong_to_the_webview_that_saved_them() {
+    let mut app = app();
+    app.clipboard().write_text("secret".to_string()).unwrap();
+    let id: u64 = app.call("save_snapshot", json!({}));
+    app.use_window("other");
+    assert_eq!(
+        app.error("restore_snapshot", json!({ "id": id })),
+        "snapshotNotFound"
+    );
+    assert_eq!(
+        app.error("discard_snapshot", json!({ "id": id })),
+        "snapshotNotFound"
+    );
+    app.use_window("main");
+    app.call::<()>("restore_snapshot", json!({ "id": id }));
+}
+
+#[test]
+fn snapshots_belong_to_the_selection_they_were_saved_from() {
+    let app = app();
+    app.call::<()>(
+        "write_text",
+        json!({ "text": "selected", "selection": "primary" }),
+    );
+    let id: u64 = app.call("save_snapshot", json!({ "selection": "primary" }));
+    for command in ["restore_snapshot", "discard_snapshot"] {
+        assert_eq!(app.error(command, json!({ "id": id })), "snapshotNotFound");
+    }
+    app.call::<()>(
+        "write_text",
+        json!({ "text": "borrowed", "selection": "primary" }),
+    );
+    app.call::<()>(
+        "restore_snapshot",
+        json!({ "id": id, "selection": "primary" }),
+    );
+    let text: String = app.call("read_text", json!({ "selection": "primary" }));
+    assert_eq!(text, "selected");
+}
+
+#[test]
+fn scope_applies_to_snapshots() {
+    let app = scoped(vec![], vec![json!({ "format": "html", "access": "write" })]);
+    app.clipboard()
+        .write_html_and_text("<b>x</b>".to_string(), "x".to_string())
+        .unwrap();
+    let id: u64 = app.call("save_snapshot", json!({}));
+    app.call::<()>("write_text", json!({ "text": "borrowed" }));
+    assert_eq!(
+        app.error("restore_snapshot", json!({ "id": id })),
+        "scopeDenied"
+    );
+    // the denied restore keeps the snapshot
+    app.call::<()>("discard_snapshot", json!({ "id": id }));
+    assert_eq!(app.call::<String>("read_text", json!({})), "borrowed");
+}
+
+#[test]
+fn monitor_runs_until_every_webview_stopped_it() {
+    let mut app = app();
+    app.call::<()>("start_monitor", json!({ "onEvent": "__CHANNEL__:1" }));
+    app.use_window("other");
+    // a second webview joins the running monitor
+    app.call::<()>("start_monitor", json!({ "onEvent": "__CHANNEL__:1" }));
+    app.use_window("main");
+    app.call::<()>("stop_monitor", json!({}));
+    assert!(app.call::<bool>("is_monitor_running", json!({})));
+    app.use_window("other");
+    app.call::<()>("stop_monitor", json!({}));
+    assert!(!app.call::<bool>("is_monitor_running", json!({})));
+}
+
+#[test]
+fn monitor_events_reach_only_the_webviews_that_started_it() {
+    let mut app = app();
+    app.use_window("other");
+    let (tx, heard) = std::sync::mpsc::channel();
+    // a js `listen` without a target hears every emit, whatever webview it targets
+    app.webview.listen_any(
+        "plugin:clipboard://clipboard-monitor/update",
+        move |event| {
+            let _ = tx.send(event.payload().to_string());
+        },
+    );
+    app.use_window("main");
+    app.call::<()>(
+        "start_monitor",
+        json!({ "onEvent": "__CHANNEL__:1", "options": { "text": true } }),
+    );
+    app.clipboard().write_text("x".to_string()).unwrap();
+    let sent = app.sent_to("main", Duration::from_secs(1));
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["text"], "x");
+    assert!(app.sent_to("other", Duration::ZERO).is_empty());
+    assert!(heard.try_recv().is_err());
+    app.call::<()>("stop_monitor", json!({}));
+}
+
+#[test]
+fn monitor_events_follow_the_scope_of_each_webview() {
+    let app = scoped(vec![], vec![json!({ "format": "html", "access": "read" })]);
+    let (tx, heard) = std::sync::mpsc::channel();
+    for event in [
+        "plugin:clipboard://clipboard-monitor/update",
+        "plugin:clipboard://html-changed",
+    ] {
+        let tx = tx.clone();
+        app.webview.listen_any(event, move |event| {
+            let _ = tx.send(event.payload().to_string());
+        });
+    }
+    app.call::<()>(
+        "start_monitor",
+        json!({ "onEvent": "__CHANNEL__:1", "options": { "text": true, "html": true } }),
+    );
+    app.clipboard()
+        .write_html_and_text("<b>x</b>".to_string(), "x".to_string())
+        .unwrap();
+    let sent = app.sent_to("main", Duration::from_secs(1));
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["text"], "x");
+    // html may not be read by the webview, no listener of it gets the html either
+    assert!(sent[0].get("html").is_none());
+    assert!(heard.try_recv().is_err());
+    app.call::<()>("stop_monitor", json!({}));
+}
+
+#[test]
+fn images_carry_their_size_in_a_header() {
+    let app = app();
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgba8(3, 2)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    app.call::<()>("write_image_binary", json!({ "bytes": png.into_inner() }));
+    let body = app.bytes("read_image", json!({ "encoding": { "format": "rgba" } }));
+    let u32_at = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+    assert_eq!((u32_at(0), u32_at(4)), (3, 2));
+    let mime_len = u32_at(8) as usize;
+    assert_eq!(&body[12..12 + mime_len], b"application/octet-stream");
+    assert_eq!(body.len() - 12 - mime_len, 3 * 2 * 4);
+}
+
+#[test]
+fn scope_limits_formats_and_sizes() {
+    let app = scoped(
+        vec![json!({ "format": "text", "maxSize": 5 })],
+        vec![json!({ "format": "html" })],
+    );
+    app.call::<()>("write_text", json!({ "text": "short" }));
+    assert_eq!(
+        app.error("write_text", json!({ "text": "too long" })),
+        "payloadTooLarge"
+    );
+    assert_eq!(
+        app.error("write_html", json!({ "html": "<b>" })),
+        "scopeDenied"
+    );
+    // a raw buffer cannot get around the typed format
+    assert_eq!(
+        app.error("read_buffer", json!({ "format": "text/html" })),
+        "scopeDenied"
+    );
+    app.clipboard()
+        .write_html_and_text("<b>x</b>".to_string(), "x".to_string())
+        .unwrap();
+    let snapshot: Value = app.call("read_all", json!({}));
+    assert_eq!(snapshot["text"], "x");
+    assert!(snapshot["html"].is_null());
+}
+
+#[test]
+fn available_formats_leave_out_what_may_not_be_read() {
+    let app = scoped(vec![], vec![json!({ "format": "html", "access": "read" })]);
+    app.clipboard()
+        .write_html_and_text("<b>x</b>".to_string(), "x".to_string())
+        .unwrap();
+    let formats: Vec<Value> = app.call("available_formats", json!({}));
+    let names: Vec<_> = formats.iter().map(|format| &format["name"]).collect();
+    assert_eq!(names, ["text/plain"]);
+    let types: Value = app.call("available_types", json!({}));
+    assert_eq!(
+        (&types["text"], &types["html"]),
+        (&json!(true), &json!(false))
+    );
+    assert!(!app.call::<bool>("has_html", json!({})));
+}
+
+#[test]
+fn scope_filters_file_paths() {
+    let dir = std::fs::canonicalize(std::env::temp_dir())
+        .unwrap()
+        .join(format!("clipboard-scope-{}", std::process::id()));
+    for sub in ["allowed", "other"] {
+        std::fs::create_dir_all(dir.join(sub)).unwrap();
+        std::fs::write(dir.join(sub).join("a.txt"), "a").unwrap();
+    }
+    let uri = |sub: &str| {
+        tauri::Url::from_file_path(dir.join(sub).join("a.txt"))
+            .unwrap()
+            .to_string()
+    };
+    let app = scoped(
+        vec![json!({ "path": format!("{}/allowed/**", dir.display()) })],
+        vec![],
+    );
+    app.call::<()>("write_files_uris", json!({ "filesUris": [uri("allowed")] }));
+    assert_eq!(
+        app.error("write_files_uris", json!({ "filesUris": [uri("other")] })),
+        "scopeDenied"
+    );
+    app.clipboard()
+        .write_files_uris(vec![uri("allowed"), uri("other")])
+        .unwrap();
+    assert_eq!(
+        app.call::<Vec<String>>("read_files_uris", json!({})),
+        [uri("allowed")]
+    );
+    // read_files returns the same files as paths
+    assert_eq!(
+        app.call::<Vec<String>>("read_files", json!({})),
+        [dir.join("allowed").join("a.txt").display().to_string()]
+    );
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn gesture_timeout_needs_a_recent_gesture() {
+    let policy = ReadPolicy {
+        gesture_timeout: Some(std::time::Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let app = app_with(
+        Builder::new().read_policy(policy),
+        ExecutionContext::Local,
+        ResolvedScope::default(),
+    );
+    app.clipboard().write_text("secret".to_string()).unwrap();
+    assert_eq!(app.error("read_text", json!({})), "accessDenied");
+    // what is on the clipboard is not told either
+    assert_eq!(app.error("available_types", json!({})), "accessDenied");
+    assert_eq!(app.error("available_formats", json!({})), "accessDenied");
+    app.call::<()>("report_user_gesture", json!({}));
+    assert_eq!(app.call::<String>("read_text", json!({})), "secret");
+}
+
+#[test]
+fn commands_need_a_capability() {
+    let app = app();
+    // allowed for local calls only
+    assert!(app
+        .request(
+            "read_text",
+            REMOTE_URL,
+            InvokeBody::Json(json!({})),
+            Default::default()
+        )
+        .is_err());
+    assert!(app.invoke("has_text", json!({})).is_err());
+}
+
+#[test]
+fn deny_remote_judges_the_calling_origin() {
+    let policy = ReadPolicy {
+        deny_remote: true,
+        ..Default::default()
+    };
+    let remote = ExecutionContext::Remote {
+        url: "https://example.com/*".parse().unwrap(),
+    };
+    let app = app_with(
+        Builder::new().read_policy(policy),
+        remote,
+        ResolvedScope::default(),
+    );
+    app.clipboard().write_text("secret".to_string()).unwrap();
+    let read = app.request(
+        "read_text",
+        REMOTE_URL,
+        InvokeBody::Json(json!({})),
+        Default::default(),
+    );
+    assert_eq!(read.unwrap_err()["kind"], "accessDenied");
+    // writing is not a read
+    app.request(
+        "write_text",
+        REMOTE_URL,
+        InvokeBody::Json(json!({ "text": "x" })),
+        Default::default(),
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn history_entries_are_scoped() {
+    use std::time::Duration;
+    use tauri_plugin_clipboard::HistoryConfig;
+
+    let path = std::env::temp_dir().join(format!("clipboard-history-{}.json", std::process::id()));
+    let builder = Builder::new().history(HistoryConfig {
+        path: Some(path.clone()),
+        ..Default::default()
+    });
+    let scope = ResolvedScope {
+        allow: vec![],
+        deny: vec![json!({ "format": "html" }).into()],
+    };
+    let app = app_with(builder, ExecutionContext::Local, scope);
+    let clipboard = app.clipboard();
+    let changes = clipboard.subscribe().unwrap();
+    clipboard.start_monitor(app.app.handle().clone()).unwrap();
+    clipboard.write_text("kept".to_string()).unwrap();
+    changes.recv_timeout(Duration::from_secs(1)).unwrap();
+    clipboard.write_html("<b>hidden</b>".to_string()).unwrap();
+    changes.recv_timeout(Duration::from_secs(1)).unwrap();
+    let entries: Vec<Value> = app.call("history_list", json!({}));
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["text"], "kept");
+    // entries with formats that may not be written cannot be copied back
+    let hidden = clipboard
+        .history_list()
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.html.is_some())
+        .unwrap();
+    assert_eq!(
+        app.error("history_copy", json!({ "id": hidden.id })),
+        "scopeDenied"
+    );
+    // entries the scope hides cannot be pinned or deleted either
+    for (command, args) in [
+        ("history_pin", json!({ "id": hidden.id, "pinned": true })),
+        ("history_delete", json!({ "id": hidden.id })),
+    ] {
+        assert_eq!(app.error(command, args), "scopeDenied");
+    }
+    assert!(clipboard.history_get(hidden.id).is_ok());
+    let kept = entries[0]["id"].as_u64().unwrap();
+    app.call::<()>("history_pin", json!({ "id": kept, "pinned": true }));
+    assert!(clipboard.history_get(kept).unwrap().pinned);
+    app.call::<()>("history_copy", json!({ "id": entries[0]["id"] }));
+    clipboard.stop_monitor(app.app.handle().clone()).unwrap();
+    let _ = std::fs::remove_file(path);
+}
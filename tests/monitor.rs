@@ -0,0 +1,180 @@
+//! The monitor and the write bookkeeping of the plugin, on the mock runtime with the clipboard kept in memory.
+
+use std::time::Duration;
+use tauri::{
+    test::{mock_builder, mock_context, noop_assets, MockRuntime},
+    App, Manager,
+};
+use tauri_plugin_clipboard::{
+    Builder, ChangeOrigin, Clipboard, DebounceEdge, MemoryBackend, MonitorOptions, PersistOnExit,
+    Subscription,
+};
+
+const WAIT: Duration = Duration::from_millis(500);
+
+fn app(builder: Builder) -> App<MockRuntime> {
+    mock_builder()
+        .plugin(builder.backend(MemoryBackend::new()).build())
+        .build(mock_context(noop_assets()))
+        .unwrap()
+}
+
+/// Subscribe to the clipboard of `app` and start its monitor with text in the events.
+fn monitor(app: &App<MockRuntime>) -> Subscription {
+    let clipboard = app.state::<Clipboard>();
+    let changes = clipboard.subscribe().unwrap();
+    let options = MonitorOptions {
+        text: true,
+        ..Default::default()
+    };
+    clipboard
+        .start_monitor_with_options(app.handle().clone(), options)
+        .unwrap();
+    changes
+}
+
+/// Write `text` the way another app would, bypassing the plugin's bookkeeping.
+fn copy_elsewhere(app: &App<MockRuntime>, text: &str) {
+    let clipboard = app.state::<Clipboard>();
+    let backend = clipboard.clipboard.lock().unwrap();
+    backend.set_text(text.to_string()).unwrap();
+}
+
+#[test]
+fn identical_changes_are_deduplicated() {
+    let app = app(Builder::new());
+    let changes = monitor(&app);
+    copy_elsewhere(&app, "same");
+    assert_eq!(
+        changes.recv_timeout(WAIT).unwrap().text.as_deref(),
+        Some("same")
+    );
+    copy_elsewhere(&app, "same");
+    assert!(changes.recv_timeout(WAIT).is_none());
+    copy_elsewhere(&app, "other");
+    assert_eq!(changes.recv_timeout(WAIT).unwrap().seq, 2);
+}
+
+#[test]
+fn deduplication_can_be_turned_off() {
+    let app = app(Builder::new().deduplicate(false));
+    let changes = monitor(&app);
+    copy_elsewhere(&app, "same");
+    copy_elsewhere(&app, "same");
+    assert!(changes.recv_timeout(WAIT).is_some());
+    assert!(changes.recv_timeout(WAIT).is_some());
+}
+
+#[test]
+fn own_writes_are_tagged() {
+    let app = app(Builder::new());
+    let changes = monitor(&app);
+    app.state::<Clipboard>()
+        .write_text("mine".to_string())
+        .unwrap();
+    assert_eq!(
+        changes.recv_timeout(WAIT).unwrap().origin,
+        ChangeOrigin::Plugin
+    );
+    copy_elsewhere(&app, "theirs");
+    assert_eq!(
+        changes.recv_timeout(WAIT).unwrap().origin,
+        ChangeOrigin::External
+    );
+}
+
+#[test]
+fn images_are_told_apart_without_attaching_them() {
+    let app = app(Builder::new());
+    let changes = monitor(&app);
+    let clipboard = app.state::<Clipboard>();
+    let png = |pixel: [u8; 4]| {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(2, 2, image::Rgba(pixel))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    };
+    clipboard.write_image_binary(png([255, 0, 0, 255])).unwrap();
+    let event = changes.recv_timeout(WAIT).unwrap();
+    assert_eq!((event.origin, event.image), (ChangeOrigin::Plugin, None));
+    clipboard.write_image_binary(png([255, 0, 0, 255])).unwrap();
+    assert!(changes.recv_timeout(WAIT).is_none());
+    clipboard.write_image_binary(png([0, 0, 255, 255])).unwrap();
+    assert!(changes.recv_timeout(WAIT).unwrap().formats.image);
+}
+
+#[test]
+fn bursts_are_debounced() {
+    let app = app(Builder::new().debounce(Duration::from_millis(100), DebounceEdge::Trailing));
+    let changes = monitor(&app);
+    for text in ["1", "2", "3"] {
+        copy_elsewhere(&app, text);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(
+        changes.recv_timeout(WAIT).unwrap().text.as_deref(),
+        Some("3")
+    );
+    assert!(changes.recv_timeout(WAIT).is_none());
+}
+
+#[test]
+fn sensitive_text_is_cleared_at_exit() {
+    let app = app(Builder::new());
+    let clipboard = app.state::<Clipboard>();
+    clipboard
+        .write_sensitive_text("otp".to_string(), None)
+        .unwrap();
+    clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+    assert!(!clipboard.has_text().unwrap());
+}
+
+#[test]
+fn content_copied_since_survives_exit() {
+    let app = app(Builder::new().expire_after(Duration::from_secs(60)));
+    let clipboard = app.state::<Clipboard>();
+    clipboard
+        .write_sensitive_text("otp".to_string(), None)
+        .unwrap();
+    copy_elsewhere(&app, "copied later");
+    clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+    assert_eq!(clipboard.read_text().unwrap(), "copied later");
+    // regular content still waiting to expire is cleared as well
+    clipboard.write_text("regular".to_string()).unwrap();
+    clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+    assert!(!clipboard.has_text().unwrap());
+}
+
+#[test]
+fn keep_expiring_on_exit_clears_only_sensitive_text() {
+    let app = app(Builder::new()
+        .expire_after(Duration::from_secs(60))
+        .keep_expiring_on_exit(true));
+    let clipboard = app.state::<Clipboard>();
+    clipboard.write_text("regular".to_string()).unwrap();
+    clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+    assert_eq!(clipboard.read_text().unwrap(), "regular");
+    clipboard
+        .write_sensitive_text("otp".to_string(), None)
+        .unwrap();
+    clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+    assert!(!clipboard.has_text().unwrap());
+}
+
+#[test]
+fn sensitive_text_kept_without_expiry_is_cleared_at_exit() {
+    for builder in [
+        Builder::new(),
+        Builder::new().sensitive_clear_after(Duration::ZERO),
+    ] {
+        let app = app(builder);
+        let clipboard = app.state::<Clipboard>();
+        clipboard
+            .write_sensitive_text("otp".to_string(), Some(Duration::ZERO))
+            .unwrap();
+        assert_eq!(clipboard.read_text().unwrap(), "otp");
+        clipboard.persist_on_exit(PersistOnExit::Never).unwrap();
+        assert!(!clipboard.has_text().unwrap());
+    }
+}
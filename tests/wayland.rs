@@ -0,0 +1,78 @@
+//! Runs against a headless compositor with data-control, e.g.
+//! `WLR_BACKENDS=headless sway` and then `WAYLAND_DISPLAY=wayland-1 cargo test --test wayland -- --ignored`.
+//! CI does the same in the `wayland` job of `.github/workflows/test.yml`.
+#![cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+
+use std::{sync::mpsc, time::Duration};
+use tauri_plugin_clipboard::{ClipboardBackend, ClipboardSelection, WaylandBackend};
+
+fn connect() -> WaylandBackend {
+    let display = std::env::var("WAYLAND_DISPLAY").expect("WAYLAND_DISPLAY names the compositor");
+    WaylandBackend::connect(&display).expect("the compositor offers data-control")
+}
+
+#[test]
+#[ignore = "needs a headless compositor with data-control"]
+fn reads_what_another_client_wrote() {
+    let writer = connect();
+    let reader = connect();
+    writer.set_text("from the writer".to_string()).unwrap();
+    writer.set_html("<b>bold</b>".to_string()).unwrap();
+    assert_eq!(reader.get_html().unwrap(), "<b>bold</b>");
+    assert!(reader
+        .available_formats()
+        .unwrap()
+        .iter()
+        .any(|format| format == "text/html"));
+}
+
+#[test]
+#[ignore = "needs a headless compositor with data-control"]
+fn watch_sees_changes_of_another_client() {
+    let writer = connect();
+    let watcher = connect();
+    let (changed, changes) = mpsc::channel();
+    let handle = watcher
+        .watch(Box::new(move || {
+            let _ = changed.send(());
+        }))
+        .unwrap();
+    writer.set_text("changed".to_string()).unwrap();
+    changes.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert_eq!(watcher.get_text().unwrap(), "changed");
+    handle.stop();
+}
+
+#[test]
+#[ignore = "needs a headless compositor with data-control"]
+fn primary_selection_is_separate() {
+    let backend = connect();
+    let Some(primary) = backend.selection(ClipboardSelection::Primary) else {
+        return;
+    };
+    backend.set_text("clipboard".to_string()).unwrap();
+    primary.set_text("primary".to_string()).unwrap();
+    assert_eq!(backend.get_text().unwrap(), "clipboard");
+    assert_eq!(primary.get_text().unwrap(), "primary");
+}
+
+#[test]
+#[ignore = "needs a headless compositor with data-control and no clipboard manager"]
+fn contents_vanish_with_the_writer() {
+    // see `PersistOnExit`: nothing keeps Wayland contents once the app that wrote them is gone
+    let writer = connect();
+    writer.set_text("short lived".to_string()).unwrap();
+    let reader = connect();
+    assert_eq!(reader.get_text().unwrap(), "short lived");
+    drop(writer);
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(reader.get_text().is_err());
+}